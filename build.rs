@@ -0,0 +1,9 @@
+// Generates src/networking.rs's types from the protocol schema. protoc comes from a vendored
+// binary so the build doesn't need it installed or any network access.
+fn main() -> std::io::Result<()> {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("No vendored protoc for this platform");
+    std::env::set_var("PROTOC", protoc);
+
+    println!("cargo:rerun-if-changed=proto/networking.proto");
+    prost_build::compile_protos(&["proto/networking.proto"], &["proto/"])
+}
@@ -0,0 +1,99 @@
+use crate::rules;
+use crate::session::PlayedMove;
+use crate::settings::{Announcements, Settings};
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+use log::warn;
+use std::process::Command;
+
+// The square the way a screen reader should say it, e.g. "g1"
+fn square(pos: Pos) -> String {
+    crate::notation::pos_name(pos)
+}
+
+fn piece_name(piece: PieceType) -> &'static str {
+    match piece {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+fn color_name(color: Color) -> &'static str {
+    if color == Color::White { "White" } else { "Black" }
+}
+
+// A move that has just been made on `board` in words, e.g. "White knight g1 to f3" or "Black
+// pawn e5 takes d4, check"
+pub fn describe_move(played: &PlayedMove, board: &Board) -> String {
+    let color = if board.turn == Color::White { Color::Black } else { Color::White };
+    let mut text = if played.san.starts_with("O-O-O") {
+        format!("{} castles queenside", color_name(color))
+    } else if played.san.starts_with("O-O") {
+        format!("{} castles kingside", color_name(color))
+    } else {
+        // A promoted pawn already is the new piece
+        let piece = match played.promotion {
+            Some(_) => PieceType::Pawn,
+            None => board.board[played.to.y as usize][played.to.x as usize].as_ref().map_or(PieceType::Pawn, |p| p.get_type()),
+        };
+        let action = if played.san.contains('x') { "takes" } else { "to" };
+        format!("{} {} {} {} {}", color_name(color), piece_name(piece), square(played.from), action, square(played.to))
+    };
+    if let Some(promotion) = played.promotion {
+        text.push_str(&format!(" and promotes to a {}", piece_name(promotion)));
+    }
+    if rules::outcome(board).is_none() && rules::is_in_check(board, board.turn) {
+        text.push_str(", check");
+    }
+    text
+}
+
+// Where every piece stands, strongest first, e.g. "White: king on g1, rooks on a1 and f1. Black:
+// ... White to move."
+pub fn describe_position(board: &Board) -> String {
+    let order = [PieceType::King, PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::Pawn];
+    let mut text = String::new();
+    for color in [Color::White, Color::Black] {
+        let pieces = rules::pieces_of(board, color);
+        let mut groups = Vec::new();
+        for piece in order {
+            let squares: Vec<String> = pieces.iter().filter(|(_, t)| *t == piece).map(|&(p, _)| square(p)).collect();
+            match squares.as_slice() {
+                [] => (),
+                [one] => groups.push(format!("{} on {}", piece_name(piece), one)),
+                [rest @ .., last] => groups.push(format!("{}s on {} and {}", piece_name(piece), rest.join(", "), last)),
+            }
+        }
+        text.push_str(&format!("{}: {}. ", color_name(color), groups.join(", ")));
+    }
+    text.push_str(&format!("{} to move.", color_name(board.turn)));
+    text
+}
+
+// Says `text` the way the settings ask for, if at all. The text is added as the last argument of
+// the speech command, which runs in the background so slow speech doesn't hold up the game.
+pub fn announce(settings: &Settings, text: &str) {
+    match settings.announcements {
+        Announcements::Off => (),
+        Announcements::Print => println!("{}", text),
+        Announcements::Speech => {
+            let command = settings.speech_command.clone();
+            let text = text.to_string();
+            std::thread::spawn(move || {
+                let mut words = command.split_whitespace();
+                let program = match words.next() {
+                    Some(program) => program,
+                    None => return,
+                };
+                if let Err(e) = Command::new(program).args(words).arg(&text).status() {
+                    warn!("Failed to run the speech command {}: {}", command, e);
+                }
+            });
+        }
+    }
+}
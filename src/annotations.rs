@@ -0,0 +1,59 @@
+use crate::networking::{self, AnnotationColor};
+use crate::notation::{pos_to_square, square_to_pos};
+use chess::util::Pos;
+
+// An arrow between two squares, or a circle around one when both are the same
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Annotation {
+    pub from: Pos,
+    pub to: Pos,
+    pub color: AnnotationColor,
+}
+
+impl Annotation {
+    pub fn is_circle(&self) -> bool {
+        self.from == self.to
+    }
+
+    pub fn to_proto(self) -> networking::Annotation {
+        networking::Annotation {
+            from_square: pos_to_square(self.from),
+            to_square: pos_to_square(self.to),
+            color: self.color as i32,
+        }
+    }
+
+    // None for squares off the board
+    pub fn from_proto(a: &networking::Annotation) -> Option<Annotation> {
+        if a.from_square > 63 || a.to_square > 63 {
+            return None;
+        }
+        Some(Annotation {
+            from: square_to_pos(a.from_square),
+            to: square_to_pos(a.to_square),
+            color: AnnotationColor::from_i32(a.color).unwrap_or(AnnotationColor::Green),
+        })
+    }
+}
+
+// The color modifier keys pick, like in most chess GUIs: green without any, red with shift (or
+// ctrl), blue with alt and yellow with both
+pub fn color_for(shift: bool, alt: bool) -> AnnotationColor {
+    match (shift, alt) {
+        (false, false) => AnnotationColor::Green,
+        (true, false) => AnnotationColor::Red,
+        (false, true) => AnnotationColor::Blue,
+        (true, true) => AnnotationColor::Yellow,
+    }
+}
+
+// Drawing the same arrow or circle again takes it away, drawing it in another color recolors it
+pub fn toggle(annotations: &mut Vec<Annotation>, annotation: Annotation) {
+    match annotations.iter().position(|a| a.from == annotation.from && a.to == annotation.to) {
+        Some(i) if annotations[i].color == annotation.color => {
+            annotations.remove(i);
+        }
+        Some(i) => annotations[i].color = annotation.color,
+        None => annotations.push(annotation),
+    }
+}
@@ -0,0 +1,36 @@
+use chess_gui::logging;
+use chess_gui::server::Server;
+use chess_gui::tls;
+use std::net::TcpListener;
+
+pub fn main() {
+    // --tls, --log and --log-file can be given anywhere, everything else is positional
+    let mut args: Vec<String> = std::env::args().collect();
+    let (filter, log_file) = logging::take_options(&mut args);
+    if let Err(e) = logging::init(filter.as_deref(), log_file.as_deref()) {
+        panic!("Failed to set up logging: {}", e);
+    }
+    logging::set_role("server");
+    let use_tls = args.iter().any(|a| a == "--tls");
+    let mut args = args.into_iter().filter(|a| a != "--tls");
+    // Skip path to program
+    let _ = args.next();
+    let address = args.next().unwrap_or_else(|| "0.0.0.0:1337".to_string());
+
+    let listener = TcpListener::bind(&address).expect("Failed to bind address");
+    listener
+        .set_nonblocking(true)
+        .expect("Failed to set listener to non blocking");
+    // Not logged since players need these whatever the log level is
+    println!("Listening on {}", address);
+
+    let tls = if use_tls {
+        let identity = tls::Identity::load_or_generate(&tls::default_dir()).expect("Failed to load certificate");
+        println!("TLS certificate fingerprint: {}", identity.fingerprint());
+        Some(identity.server_config().expect("Failed to set up TLS"))
+    } else {
+        None
+    };
+
+    Server::new(listener, tls).run()
+}
@@ -0,0 +1,121 @@
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess_gui::render::{self, BoardView, Painter, Point, Rect, Rgba};
+use ggez::graphics::{self, Canvas, Color, DrawMode, Image, MeshBuilder};
+use ggez::Context;
+use glam::Vec2;
+
+use crate::scene::Shared;
+use crate::utils::*;
+
+fn color(c: Rgba) -> Color {
+    Color::new(c.r, c.g, c.b, c.a)
+}
+
+fn point(p: Point) -> Vec2 {
+    Vec2::new(p.0, p.1)
+}
+
+fn rect(r: Rect) -> graphics::Rect {
+    graphics::Rect { x: r.x, y: r.y, w: r.w, h: r.h }
+}
+
+fn piece_image(pieces: &Imglib, piece: PieceType, c: chess::util::Color) -> &Image {
+    let white = c == chess::util::Color::White;
+    match piece {
+        PieceType::Pawn => if white { &pieces.white_pawn } else { &pieces.black_pawn },
+        PieceType::Knight => if white { &pieces.white_knight } else { &pieces.black_knight },
+        PieceType::Bishop => if white { &pieces.white_bishop } else { &pieces.black_bishop },
+        PieceType::Rook => if white { &pieces.white_rook } else { &pieces.black_rook },
+        PieceType::Queen => if white { &pieces.white_queen } else { &pieces.black_queen },
+        PieceType::King => if white { &pieces.white_king } else { &pieces.black_king },
+    }
+}
+
+// Paints on a ggez canvas. Shapes are collected into one mesh until a piece has to be drawn on
+// top of them.
+struct CanvasPainter<'a> {
+    ctx: &'a mut Context,
+    canvas: &'a mut Canvas,
+    pieces: &'a Imglib,
+    mb: MeshBuilder,
+    empty: bool,
+}
+
+impl<'a> CanvasPainter<'a> {
+    fn flush(&mut self) {
+        if !self.empty {
+            let mesh = graphics::Mesh::from_data(&*self.ctx, self.mb.build());
+            self.canvas.draw(&mesh, graphics::DrawParam::new().image_scale(false));
+            self.mb = MeshBuilder::new();
+            self.empty = true;
+        }
+    }
+
+    fn mesh(&mut self) -> &mut MeshBuilder {
+        self.empty = false;
+        &mut self.mb
+    }
+}
+
+impl<'a> Painter for CanvasPainter<'a> {
+    fn fill_rect(&mut self, r: Rect, c: Rgba) {
+        self.mesh().rectangle(DrawMode::fill(), rect(r), color(c)).expect("Error in building mesh");
+    }
+
+    fn stroke_rect(&mut self, r: Rect, width: f32, c: Rgba) {
+        self.mesh().rectangle(DrawMode::stroke(width), rect(r), color(c)).expect("Error in building mesh");
+    }
+
+    fn fill_circle(&mut self, center: Point, radius: f32, c: Rgba) {
+        self.mesh().circle(DrawMode::fill(), point(center), radius, 0.1, color(c)).expect("Error in building mesh");
+    }
+
+    fn stroke_circle(&mut self, center: Point, radius: f32, width: f32, c: Rgba) {
+        self.mesh().circle(DrawMode::stroke(width), point(center), radius, 0.1, color(c)).expect("Error in building mesh");
+    }
+
+    fn line(&mut self, from: Point, to: Point, width: f32, c: Rgba) {
+        self.mesh().line(&[point(from), point(to)], width, color(c)).expect("Error in building mesh");
+    }
+
+    fn fill_polygon(&mut self, points: &[Point], c: Rgba) {
+        let points: Vec<Vec2> = points.iter().map(|&p| point(p)).collect();
+        self.mesh().polygon(DrawMode::fill(), &points, color(c)).expect("Error in building mesh");
+    }
+
+    fn piece(&mut self, piece: PieceType, c: chess::util::Color, square: Rect) {
+        self.flush();
+        let img = piece_image(self.pieces, piece, c);
+        let scale = Vec2::new(square.w / img.width() as f32, square.h / img.height() as f32);
+        self.canvas.draw(img, graphics::DrawParam::new().dest(Vec2::new(square.x, square.y)).scale(scale));
+    }
+}
+
+// Draws the board and everything on it the way `view` says
+pub fn draw_view(ctx: &mut Context, canvas: &mut Canvas, shared: &Shared, view: &BoardView) {
+    let mut painter = CanvasPainter { ctx, canvas, pieces: &shared.pieces, mb: MeshBuilder::new(), empty: true };
+    render::paint(view, &mut painter);
+    painter.flush();
+}
+
+// The settings that change how the board looks, with nothing on the board but the pieces
+pub fn view<'a>(shared: &Shared, board: &'a Board, flipped: bool) -> BoardView<'a> {
+    BoardView {
+        large_outlines: shared.settings.large_outlines,
+        ..BoardView::plain(board, flipped, shared.settings.theme, CELL_DIMENSIONS.0 as f32)
+    }
+}
+
+// Draws a position without any selection or highlights, e.g. in the review
+pub fn draw_board(ctx: &mut Context, canvas: &mut Canvas, shared: &Shared, board: &Board, flipped: bool) {
+    draw_view(ctx, canvas, shared, &view(shared, board, flipped));
+}
+
+// A title and a line of help at the top of a screen that isn't showing the board
+pub fn draw_header(canvas: &mut Canvas, title: &str, help: &str) {
+    let title = graphics::Text::new(title);
+    canvas.draw(&title, graphics::DrawParam::new().dest(Vec2::new(16.0, 16.0)).color(Color::WHITE));
+    let help = graphics::Text::new(help);
+    canvas.draw(&help, graphics::DrawParam::new().dest(Vec2::new(16.0, 64.0)).color(Color::WHITE));
+}
@@ -0,0 +1,108 @@
+use chess_gui::networking::ChatMessage;
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::Context;
+use glam::Vec2;
+
+use crate::utils::*;
+
+const LINE_HEIGHT: f32 = 24.0;
+const MARGIN: f32 = 12.0;
+// Room for the player names, the title and the mute button above the messages
+const TOP: f32 = PLAYERS_HEIGHT as f32;
+const HEADER_HEIGHT: f32 = TOP + 48.0;
+const INPUT_HEIGHT: f32 = 40.0;
+const MUTE_BUTTON: graphics::Rect = graphics::Rect {
+    x: SCREEN_DIMENSIONS.0 as f32 + PANEL_WIDTH as f32 - 100.0,
+    y: TOP + MARGIN,
+    w: 100.0 - MARGIN,
+    h: 28.0,
+};
+
+struct ChatLine {
+    time: String,
+    sender: String,
+    text: String,
+    system: bool,
+}
+
+// The chat pane in the side panel
+pub struct Chat {
+    lines: Vec<ChatLine>,
+    pub input: String,
+    // Hides what other people write, system messages are still shown
+    pub muted: bool,
+}
+
+impl Chat {
+    pub fn new() -> Chat {
+        Chat {
+            lines: Vec::new(),
+            input: String::new(),
+            muted: false,
+        }
+    }
+
+    // `own` is set for messages we sent ourselves, they are shown even when muted
+    pub fn push(&mut self, message: ChatMessage, own: bool) {
+        if self.muted && !own && !message.system {
+            return;
+        }
+        self.lines.push(ChatLine {
+            time: format_time(message.sent_at),
+            sender: message.sender,
+            text: message.text,
+            system: message.system,
+        });
+    }
+
+    pub fn is_on_mute_button(&self, x: f32, y: f32) -> bool {
+        MUTE_BUTTON.contains(Vec2::new(x, y))
+    }
+
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, title: &str, enabled: bool) {
+        let left = SCREEN_DIMENSIONS.0 as f32;
+        let height = SCREEN_DIMENSIONS.1 as f32;
+
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: left, y: 0.0, w: PANEL_WIDTH as f32, h: height },
+            Color::from_rgb(30, 30, 30)).expect("Error in building mesh");
+        mb.rectangle(DrawMode::fill(), MUTE_BUTTON, Color::from_rgb(70, 70, 70)).expect("Error in building mesh");
+        mb.rectangle(
+            DrawMode::stroke(2.0),
+            graphics::Rect { x: left + MARGIN, y: height - INPUT_HEIGHT - MARGIN, w: PANEL_WIDTH as f32 - 2.0 * MARGIN, h: INPUT_HEIGHT },
+            Color::WHITE).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        let text = graphics::Text::new(title);
+        canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(left + MARGIN, TOP + MARGIN + 6.0)).color(Color::WHITE));
+        let text = graphics::Text::new(if self.muted { "Unmute" } else { "Mute" });
+        canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(MUTE_BUTTON.x + 8.0, MUTE_BUTTON.y + 6.0)).color(Color::WHITE));
+
+        // Newest messages at the bottom, older ones scroll out at the top
+        let rows = ((height - HEADER_HEIGHT - INPUT_HEIGHT - 2.0 * MARGIN) / LINE_HEIGHT) as usize;
+        let first = self.lines.len().saturating_sub(rows);
+        for (i, line) in self.lines[first..].iter().enumerate() {
+            let (content, color) = if line.system {
+                (format!("{} {}", line.time, line.text), Color::from_rgb(180, 180, 120))
+            } else {
+                (format!("{} {}: {}", line.time, line.sender, line.text), Color::WHITE)
+            };
+            let text = graphics::Text::new(content);
+            let dst = Vec2::new(left + MARGIN, HEADER_HEIGHT + i as f32 * LINE_HEIGHT);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(color));
+        }
+
+        let input = if enabled { format!("{}_", self.input) } else { "Chat isn't available".to_string() };
+        let text = graphics::Text::new(input);
+        let dst = Vec2::new(left + 2.0 * MARGIN, height - INPUT_HEIGHT - MARGIN + 10.0);
+        canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::WHITE));
+    }
+}
+
+// HH:MM of a unix timestamp, in UTC
+fn format_time(secs: u64) -> String {
+    let minutes = secs / 60;
+    format!("{:02}:{:02}", (minutes / 60) % 24, minutes % 60)
+}
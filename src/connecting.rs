@@ -0,0 +1,226 @@
+use chess_gui::error::{self, Error};
+use chess_gui::identity;
+use chess_gui::logging::{self, NETWORKING};
+use chess_gui::networking::PlayerIdentity;
+use chess_gui::recording::{Recording, Replay};
+use chess_gui::session::Session;
+use chess_gui::settings::{take_option, Settings};
+use chess_gui::stream::{Socket, Stream};
+use chess_gui::{tls, websocket};
+use ggez::graphics::Canvas;
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use log::{info, warn};
+use std::net::*;
+use std::path;
+use std::sync::mpsc;
+
+use crate::board_view::draw_header;
+use crate::game_screen::GameScreen;
+use crate::lobby::{Lobby, LobbyScreen};
+use crate::scene::{Scene, Shared, Transition};
+
+enum Connection {
+    // The host keeps listening so that spectators can join after the opponent has connected. With
+    // TLS the host shows its certificate to everyone connecting.
+    Host(TcpListener, Option<tls::Identity>),
+    // A stream to the host and a boolean indicating wether or not we are only spectating
+    Client(Stream, bool),
+    // A stream to a game server
+    Lobby(Stream),
+    // A recorded session to play back instead of connecting to anyone
+    Replay(Recording),
+}
+
+// Connects to `ip`, with TLS if asked to. Addresses starting with ws:// are connected to with a
+// WebSocket, e.g. to reach a host behind a web proxy.
+fn connect(ip: &str, use_tls: bool) -> error::Result<Stream> {
+    let (address, use_websocket) = match ip.strip_prefix("ws://") {
+        Some(address) => (address.trim_end_matches('/'), true),
+        None => (ip, false),
+    };
+    let stream = TcpStream::connect(address).map_err(failed(format!("Failed to connect to {}", address)))?;
+    let socket = if use_tls {
+        let known_peers = tls::KnownPeers::new(tls::default_dir().join("known_peers"));
+        tls::connect(stream, address, known_peers).map_err(failed("TLS handshake failed".to_string()))?
+    } else {
+        //Set TcpStream to non blocking so that we can do networking in the update thread
+        stream.set_nonblocking(true)?;
+        Socket::Tcp(stream)
+    };
+    if use_websocket {
+        // TLS is already taken care of underneath so the url always says ws
+        return websocket::connect(socket, &format!("ws://{}/", address))
+            .map_err(failed("WebSocket handshake failed".to_string()));
+    }
+    Ok(socket.into())
+}
+
+// Keeps the kind of an IO error but says what we were doing when it happened
+fn failed(what: String) -> impl FnOnce(std::io::Error) -> Error {
+    move |e| Error::Network(std::io::Error::new(e.kind(), format!("{}: {}", what, e)))
+}
+
+// The commands that connect to someone, the others are handled by main
+pub const COMMANDS: [&str; 5] = ["host", "client", "spectate", "lobby", "replay"];
+
+// The connection, who we are and where to record the session to, if anywhere. The options for
+// logging and the settings have already been taken out of `args`.
+fn get_connection(args: &[String], settings: &Settings) -> error::Result<(Connection, PlayerIdentity, Option<path::PathBuf>)> {
+    // --tls, --password and --record can be given anywhere, everything else is positional
+    let mut args = args.to_vec();
+    let use_tls = args.iter().any(|a| a == "--tls");
+    args.retain(|a| a != "--tls");
+    let password = take_option(&mut args, "--password");
+    let record = take_option(&mut args, "--record").map(path::PathBuf::from);
+    let mut args = args.into_iter();
+    // Skip path to program
+    let _ = args.next();
+
+    // Get first argument after path to program
+    let host_or_client = args.next().ok_or_else(|| {
+        Error::Input("Expected arguments: host, client 'ip', spectate 'ip', lobby 'ip' 'name', replay 'file' or pgn 'file', optionally with --tls, --password 'password', --record 'file', --log 'filter', --log-file 'file' and the settings to override, e.g. --name 'name' or --port 'port'".to_string())
+    })?;
+    let missing = |what: &str| Error::Input(format!("Expected {} after {}", what, host_or_client));
+
+    let connection = match host_or_client.as_str() {
+        // If the program is running as host we listen on the address from the settings and accept
+        // connections in the update loop.
+        "host" => {
+            let listener = TcpListener::bind(settings.address()).map_err(failed(format!("Failed to listen on {}", settings.address())))?;
+            listener.set_nonblocking(true)?;
+            let identity = if use_tls {
+                Some(tls::Identity::load_or_generate(&tls::default_dir()).map_err(failed("Failed to load certificate".to_string()))?)
+            } else {
+                None
+            };
+            Connection::Host(listener, identity)
+        }
+        // If the program is running as a client or spectator we connect to the specified IP
+        // address, or the one from the settings, and return the stream.
+        "client" | "spectate" => {
+            let ip = args.next().unwrap_or_else(|| settings.address());
+            Connection::Client(connect(&ip, use_tls)?, host_or_client == "spectate")
+        }
+        // Against a game server we pick the game to play from its lobby
+        "lobby" => {
+            let ip = args.next().unwrap_or_else(|| settings.address());
+            Connection::Lobby(connect(&ip, use_tls)?)
+        }
+        "replay" => {
+            let file = args.next().ok_or_else(|| missing("a recording"))?;
+            let recording = Recording::load(path::Path::new(&file)).map_err(failed(format!("Failed to load {}", file)))?;
+            Connection::Replay(recording)
+        }
+        // Only host, client, spectate, lobby and replay are valid arguments
+        _ => return Err(Error::Input(format!("Unknown command: {}", host_or_client))),
+    };
+
+    // The name can also follow the lobby's address
+    let name = args.next().unwrap_or_else(|| settings.name.clone());
+    let identity = identity::load(name, password).map_err(failed("Failed to load player token".to_string()))?;
+    Ok((connection, identity, record))
+}
+
+// Sets up the session for a connection and the scene that shows it
+fn start(
+    connection: Connection,
+    identity: PlayerIdentity,
+    record: Option<path::PathBuf>,
+    settings: &Settings,
+) -> error::Result<Box<dyn Scene>> {
+    let (mut session, replay, lobby) = match connection {
+        Connection::Host(listener, None) => (Session::host(listener, identity), None, None),
+        Connection::Host(listener, Some(certificate)) => {
+            let session = Session::host_tls(listener, identity, &certificate).map_err(failed("Failed to set up TLS".to_string()))?;
+            (session, None, None)
+        }
+        Connection::Client(stream, spectate) => (Session::client(stream, spectate, identity), None, None),
+        Connection::Lobby(stream) => {
+            let lobby = Lobby::new(identity::name_of(&Some(identity.clone())), settings.time_control);
+            (Session::lobby(stream, identity), None, Some(lobby))
+        }
+        Connection::Replay(recording) => {
+            let (replay, session) = Replay::new(recording);
+            (session, Some(replay), None)
+        }
+    };
+    logging::set_role(if replay.is_some() { "replay" } else { session.role().as_str() });
+    logging::set_game_id(None);
+    if let Some(path) = record {
+        session.start_recording(&path).map_err(failed("Failed to create recording".to_string()))?;
+    }
+    Ok(match lobby {
+        Some(lobby) => Box::new(LobbyScreen::new(lobby, session)),
+        None => Box::new(GameScreen::new(session, replay)),
+    })
+}
+
+type Connected = error::Result<(Connection, PlayerIdentity, Option<path::PathBuf>)>;
+
+// Shown while connecting, which can take a while when the peer doesn't answer. The connection is
+// made on another thread so that the window keeps responding.
+pub struct ConnectingScreen {
+    description: String,
+    result: mpsc::Receiver<Connected>,
+}
+
+impl ConnectingScreen {
+    // `args` is a command line like the one the program was started with
+    pub fn new(args: Vec<String>, settings: &Settings) -> ConnectingScreen {
+        let description = match args.get(1).map(String::as_str) {
+            Some("host") => format!("Starting a game on {}", settings.address()),
+            Some("replay") => format!("Loading {}", args.get(2).map_or("", String::as_str)),
+            Some(_) => format!("Connecting to {}", args.get(2).cloned().unwrap_or_else(|| settings.address())),
+            None => "Connecting".to_string(),
+        };
+        let (sender, result) = mpsc::channel();
+        let settings = settings.clone();
+        std::thread::spawn(move || {
+            // Nobody is listening anymore if the player cancelled
+            let _ = sender.send(get_connection(&args, &settings));
+        });
+        ConnectingScreen { description, result }
+    }
+}
+
+impl Scene for ConnectingScreen {
+    fn update(&mut self, _ctx: &mut Context, shared: &mut Shared) -> Transition {
+        let (connection, identity, record) = match self.result.try_recv() {
+            Ok(Ok(connected)) => connected,
+            Ok(Err(e)) => {
+                warn!(target: NETWORKING, "{}", e);
+                shared.message = Some(e.to_string());
+                return Transition::Pop;
+            }
+            Err(mpsc::TryRecvError::Empty) => return Transition::Stay,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                shared.message = Some("Connecting failed".to_string());
+                return Transition::Pop;
+            }
+        };
+        match start(connection, identity, record, &shared.settings) {
+            Ok(scene) => {
+                shared.message = None;
+                Transition::Replace(scene)
+            }
+            Err(e) => {
+                warn!(target: NETWORKING, "{}", e);
+                shared.message = Some(e.to_string());
+                Transition::Pop
+            }
+        }
+    }
+
+    fn draw(&self, _ctx: &mut Context, canvas: &mut Canvas, _shared: &Shared) {
+        draw_header(canvas, &self.description, "Escape: cancel");
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, keycode: KeyCode) -> Transition {
+        if keycode == KeyCode::Escape {
+            info!(target: NETWORKING, "Cancelled: {}", self.description);
+            return Transition::Pop;
+        }
+        Transition::Stay
+    }
+}
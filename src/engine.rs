@@ -0,0 +1,101 @@
+use crate::notation::uci_to_move;
+use chess::piece::PieceType;
+use chess::util::Pos;
+use log::{debug, warn};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+// A chess engine that speaks UCI, running as a child process. Its output is read on a thread so
+// that the GUI can poll for the engine's move every frame.
+pub struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: mpsc::Receiver<String>,
+    thinking: bool,
+}
+
+impl Engine {
+    pub fn start(path: &Path) -> std::io::Result<Engine> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| std::io::Error::new(e.kind(), format!("Failed to start {}: {}", path.display(), e)))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = Engine { child, stdin, lines, thinking: false };
+        engine.send("uci")?;
+        engine.send("ucinewgame")?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> std::io::Result<()> {
+        debug!("To engine: {}", command);
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.thinking
+    }
+
+    // Asks for a move after `moves`, in UCI notation, have been played from the position given as
+    // FEN. The moves are passed on so that the engine knows about castling rights and repetitions.
+    // The answer is picked up by `poll`.
+    pub fn go(&mut self, fen: &str, moves: &[String], think_time: Duration) -> std::io::Result<()> {
+        if moves.is_empty() {
+            self.send(&format!("position fen {}", fen))?;
+        } else {
+            self.send(&format!("position fen {} moves {}", fen, moves.join(" ")))?;
+        }
+        self.send(&format!("go movetime {}", think_time.as_millis()))?;
+        self.thinking = true;
+        Ok(())
+    }
+
+    // The engine's move once it has decided. An engine that quit is reported as UnexpectedEof.
+    pub fn poll(&mut self) -> std::io::Result<Option<(Pos, Pos, Option<PieceType>)>> {
+        loop {
+            let line = match self.lines.try_recv() {
+                Ok(line) => line,
+                Err(mpsc::TryRecvError::Empty) => return Ok(None),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "The engine quit"))
+                }
+            };
+            let best = match line.strip_prefix("bestmove ") {
+                Some(rest) => rest.split_whitespace().next().unwrap_or(""),
+                None => continue,
+            };
+            self.thinking = false;
+            match uci_to_move(best) {
+                Some(m) => return Ok(Some(m)),
+                None => {
+                    warn!("The engine answered with {}", line);
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("The engine has no move: {}", best)));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // Engines that ignore quit aren't left running
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+
+// Everything that can go wrong while setting up or playing a game. None of these should end the
+// program, the GUI shows them in the window.
+#[derive(Debug)]
+pub enum Error {
+    // Connecting, sending or receiving failed, or the peer hung up
+    Network(std::io::Error),
+    // The peer sent something that isn't one of our messages, usually because it speaks another
+    // version of the protocol
+    Decode(prost::DecodeError),
+    // Something the player asked for can't be done, e.g. an illegal move or a missing argument
+    Input(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // Wether the player should be told about the error and not only that the connection is gone
+    pub fn is_notable(&self) -> bool {
+        match self {
+            Error::Network(e) => e.kind() == std::io::ErrorKind::InvalidData,
+            Error::Decode(_) | Error::Input(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Network(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "Couldn't decode message from peer: {}", e),
+            Error::Input(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Input(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Network(e)
+    }
+}
+
+impl From<prost::DecodeError> for Error {
+    fn from(e: prost::DecodeError) -> Error {
+        Error::Decode(e)
+    }
+}
@@ -0,0 +1,62 @@
+use chess::board::Board;
+use ggez::graphics::{self, Canvas, Color, Image, ImageFormat};
+use ggez::{Context, GameError, GameResult};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board_view::draw_board;
+use crate::scene::Shared;
+use crate::utils::*;
+
+fn export_error(path: &Path, e: impl std::fmt::Display) -> GameError {
+    GameError::CustomError(format!("Failed to write {}: {}", path.display(), e))
+}
+
+// A new file in the working directory named after the time, e.g. chess-1700000000.png
+pub fn export_path(extension: &str) -> PathBuf {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    PathBuf::from(format!("chess-{}.{}", secs, extension))
+}
+
+// Draws the board offscreen into an image `size` pixels wide and high. `draw` draws the same way
+// as on the screen, the board is scaled down to fit.
+pub fn render(ctx: &mut Context, size: u32, draw: impl FnOnce(&mut Context, &mut Canvas)) -> GameResult<RgbaImage> {
+    let image = Image::new_canvas_image(&*ctx, ImageFormat::Rgba8UnormSrgb, size, size, 1);
+    let mut canvas = Canvas::from_image(&*ctx, image.clone(), Color::BLACK);
+    canvas.set_screen_coordinates(graphics::Rect::new(0.0, 0.0, SCREEN_DIMENSIONS.0 as f32, SCREEN_DIMENSIONS.1 as f32));
+    draw(ctx, &mut canvas);
+    canvas.finish(ctx)?;
+    let pixels = image.to_pixels(&*ctx)?;
+    RgbaImage::from_raw(size, size, pixels).ok_or_else(|| GameError::RenderError("The rendered image has the wrong size".to_string()))
+}
+
+pub fn save_png(ctx: &mut Context, size: u32, path: &Path, draw: impl FnOnce(&mut Context, &mut Canvas)) -> GameResult {
+    render(ctx, size, draw)?.save(path).map_err(|e| export_error(path, e))
+}
+
+// Every position of a game, one after the other, repeating forever. `delay` is how long each is
+// shown in milliseconds.
+pub fn save_gif(ctx: &mut Context, shared: &Shared, positions: &[Board], flipped: bool, size: u32, delay: u32, path: &Path) -> GameResult {
+    let mut frames = Vec::new();
+    for board in positions {
+        let image = render(ctx, size, |ctx, canvas| draw_board(ctx, canvas, shared, board, flipped))?;
+        frames.push(Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(delay, 1)));
+    }
+    let file = File::create(path).map_err(|e| export_error(path, e))?;
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).map_err(|e| export_error(path, e))?;
+    encoder.encode_frames(frames).map_err(|e| export_error(path, e))
+}
+
+// Tells the player where the export went or why it failed
+pub fn report(shared: &mut Shared, result: GameResult<PathBuf>) {
+    let message = match result {
+        Ok(path) => format!("Saved {}", path.display()),
+        Err(e) => e.to_string(),
+    };
+    shared.announce(&message);
+    shared.message = Some(message);
+}
@@ -0,0 +1,618 @@
+use chess::piece::PieceType;
+use chess::util::Pos;
+use chess_gui::accessibility;
+use chess_gui::annotations::{self, Annotation};
+use chess_gui::engine::Engine;
+use chess_gui::logging::{INPUT, NETWORKING, RENDERING};
+use chess_gui::networking::{Capability, ChatChannel};
+use chess_gui::notation::{pos_name, START_FEN};
+use chess_gui::openings::Openings;
+use chess_gui::polyglot::Book;
+use chess_gui::recording::Replay;
+use chess_gui::render::BoardView;
+use chess_gui::rules::{self, Outcome};
+use chess_gui::session::{system_message, Event, Session};
+use chess_gui::sound::Sound;
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Canvas, Color};
+use ggez::input::keyboard::{KeyCode, KeyMods};
+use ggez::{Context, GameError};
+use glam::Vec2;
+use log::{debug, info, trace, warn};
+use std::time::Duration;
+
+use crate::board_view::{draw_view, view};
+use crate::chat::Chat;
+use crate::export::{export_path, report, save_gif, save_png};
+use crate::move_entry::MoveEntry;
+use crate::review::ReviewScreen;
+use crate::scene::{Scene, Shared, Transition};
+use crate::utils::*;
+
+// How long the engine may think about each move
+const ENGINE_THINK_TIME: Duration = Duration::from_millis(1000);
+
+// The board, the players and the chat of a game, whether it is played over the network, against
+// an engine or by two players at the same computer
+pub struct GameScreen {
+    session: Session,
+    highlights: Vec<Pos>,
+    selected_pos: Option<Pos>,
+    // Set while a recorded session is played back
+    replay: Option<Replay>,
+    chat: Chat,
+    state: State,
+    // Set when playing against an engine, it plays the color the session doesn't
+    engine: Option<Engine>,
+    outcome: Option<Outcome>,
+    // Set once the start of the game has been announced
+    started: bool,
+    // Moves queued during the opponent's turn, made one at a time once it is our turn again
+    premoves: Vec<(Pos, Pos)>,
+    // The square of the next premove's piece once it has been clicked
+    premove_from: Option<Pos>,
+    // The arrows and circles drawn with the right mouse button, ours and those shared with us
+    annotations: Vec<Annotation>,
+    peer_annotations: Vec<Annotation>,
+    // Where the right mouse button went down
+    drawing_from: Option<Pos>,
+    move_entry: MoveEntry,
+    // Set when F4 moved the keyboard from the chat to the move box
+    typing_move: bool,
+    openings: Openings,
+    // The name of the opening the game started with, e.g. "C60 Ruy Lopez"
+    opening: Option<String>,
+    // The engine plays from it as long as the game is in it
+    book: Option<Book>,
+}
+
+impl GameScreen {
+    pub fn new(session: Session, replay: Option<Replay>) -> GameScreen {
+        let mut chat = Chat::new();
+        // Both players should see the same fingerprint, otherwise someone is in between
+        if let Some(fingerprint) = session.fingerprint.clone() {
+            let channel = session.chat_channel();
+            chat.push(system_message("TLS certificate fingerprint:", channel), false);
+            let (first, second) = fingerprint.split_at(fingerprint.len() / 2);
+            chat.push(system_message(first.trim(), channel), false);
+            chat.push(system_message(second.trim(), channel), false);
+        }
+        GameScreen {
+            session,
+            highlights: Vec::new(),
+            selected_pos: None,
+            replay,
+            chat,
+            state: State::Waiting,
+            engine: None,
+            outcome: None,
+            started: false,
+            premoves: Vec::new(),
+            premove_from: None,
+            annotations: Vec::new(),
+            peer_annotations: Vec::new(),
+            drawing_from: None,
+            move_entry: MoveEntry::new(),
+            typing_move: false,
+            openings: Openings::embedded(),
+            opening: None,
+            book: None,
+        }
+    }
+
+    // A game the game server has put us in
+    pub fn joined(session: Session) -> GameScreen {
+        let mut screen = GameScreen::new(session, None);
+        screen.connected();
+        screen
+    }
+
+    // Two players taking turns at the same computer
+    pub fn hot_seat() -> GameScreen {
+        let mut screen = GameScreen::new(Session::new(), None);
+        screen.state = State::Playing;
+        screen
+    }
+
+    // We play `color` and the engine the other side, from `book` while it has a move
+    pub fn against_engine(engine: Engine, color: chess::util::Color, book: Option<Book>) -> GameScreen {
+        let mut session = Session::new();
+        session.color = Some(color);
+        let mut screen = GameScreen::new(session, None);
+        screen.engine = Some(engine);
+        screen.book = book;
+        screen.state = State::Playing;
+        screen
+    }
+
+    fn connected(&mut self) {
+        self.state = State::Playing;
+        self.chat.push(system_message("Connected", self.session.chat_channel()), false);
+    }
+
+    // Wether black is drawn at the bottom
+    fn is_flipped(&self, shared: &Shared) -> bool {
+        shared.settings.orientation.is_flipped(self.session.color)
+    }
+
+    // Chat is only available in a game with someone who supports it
+    fn chat_enabled(&self) -> bool {
+        self.session.supports(Capability::Chat)
+    }
+
+    // Wether both sides have someone to play them
+    fn has_started(&self) -> bool {
+        self.state == State::Playing || (self.session.white_name.is_some() && self.session.black_name.is_some())
+    }
+
+    // Wether moves can be made from this screen at all
+    fn can_move(&self) -> bool {
+        !self.session.is_spectator && self.replay.is_none() && self.outcome.is_none()
+    }
+
+    // Typing goes into the move box when there is no chat to type into or F4 asked for it
+    fn is_typing_move(&self) -> bool {
+        self.can_move() && (self.typing_move || !self.chat_enabled())
+    }
+
+    // Makes a move picked with the mouse or typed in
+    fn play(&mut self, ctx: &Context, shared: &mut Shared, from: Pos, to: Pos, promotion: Option<PieceType>) {
+        debug!(target: INPUT, "Moving {} to {}", pos_name(from), pos_name(to));
+        let before = self.session.history.len();
+        self.session.make_move(from, to, promotion);
+        // Nobody is waited for at a shared computer
+        if self.session.color.is_some() {
+            self.state = State::Waiting;
+        }
+
+        self.highlights = Vec::new();
+        self.selected_pos = None;
+        if self.session.history.len() > before {
+            self.moved(ctx, shared);
+        }
+    }
+
+    // Makes the move typed into the move box if it is legal and our turn
+    fn submit_move(&mut self, ctx: &Context, shared: &mut Shared) {
+        if self.is_opponents_turn() {
+            self.move_entry.error = Some("It's your opponent's turn".to_string());
+        } else if let Some((from, to, promotion)) = self.move_entry.submit(&self.session.board) {
+            self.play(ctx, shared, from, to, promotion);
+        }
+        if let Some(e) = &self.move_entry.error {
+            shared.announce(e);
+        }
+    }
+
+    // Looks for mate or stalemate after a move and plays the sound for it
+    fn moved(&mut self, ctx: &Context, shared: &mut Shared) {
+        self.move_entry.refresh(&self.session.board);
+        // The names only fit games from the usual start
+        if self.session.start_fen == START_FEN {
+            let moves: Vec<String> = self.session.history.iter().map(|m| m.san.clone()).collect();
+            self.opening = self.openings.find(&moves).map(|o| format!("{} {}", o.eco, o.name));
+        }
+        if self.outcome.is_some() {
+            return;
+        }
+        if let Some(played) = self.session.history.last() {
+            shared.announce(&accessibility::describe_move(played, &self.session.board));
+        }
+        self.outcome = rules::outcome(&self.session.board);
+        if let Some(outcome) = self.outcome {
+            info!("{}", outcome.describe());
+            shared.announce(outcome.describe());
+            self.chat.push(system_message(outcome.describe(), self.session.chat_channel()), false);
+            shared.play(ctx, Sound::GameEnd);
+        } else if let Some(played) = self.session.history.last() {
+            shared.play(ctx, Sound::for_move(played, &self.session.board));
+        }
+    }
+
+    // A move from the opening book for the engine's turn, if the book knows the position
+    fn book_move(&self) -> Option<(Pos, Pos, Option<PieceType>)> {
+        let book = self.book.as_ref()?;
+        if self.session.color == Some(self.session.board.turn) || self.engine.as_ref().is_none_or(|e| e.is_thinking()) {
+            return None;
+        }
+        let mut random = [0; 4];
+        getrandom::getrandom(&mut random).ok()?;
+        let m = book.pick(&self.session.board, self.session.polyglot_key(), u32::from_ne_bytes(random))?;
+        // A book for another variant, or a broken one, could suggest anything
+        let legal = rules::legal_moves(&self.session.board).contains(&(m.from, m.to));
+        legal.then_some((m.from, m.to, m.promotion))
+    }
+
+    // Asks the engine for a move when it is its turn and makes the move it decided on. The book
+    // is asked first, the engine only once the game has left it.
+    fn update_engine(&mut self, ctx: &Context, shared: &mut Shared) {
+        let book_move = self.book_move();
+        let engine = match &mut self.engine {
+            Some(engine) if self.outcome.is_none() => engine,
+            _ => return,
+        };
+        let result = if engine.is_thinking() {
+            engine.poll()
+        } else if book_move.is_some() {
+            Ok(book_move)
+        } else if self.session.color != Some(self.session.board.turn) {
+            let moves: Vec<String> = self.session.history.iter().map(|m| m.uci()).collect();
+            engine.go(&self.session.start_fen, &moves, ENGINE_THINK_TIME).map(|()| None)
+        } else {
+            Ok(None)
+        };
+        match result {
+            Ok(Some((from, to, promotion))) => {
+                debug!("The engine plays {}{}", pos_name(from), pos_name(to));
+                let before = self.session.history.len();
+                self.session.make_move(from, to, promotion);
+                if self.session.history.len() > before {
+                    // Like a move from an opponent over the network it is our turn again
+                    self.state = State::Playing;
+                    self.moved(ctx, shared);
+                }
+            }
+            Ok(None) => (),
+            Err(e) => {
+                warn!("{}", e);
+                shared.message = Some(format!("The engine stopped working: {}", e));
+                self.engine = None;
+            }
+        }
+    }
+
+    // Wether a click queues a premove rather than moving
+    fn is_opponents_turn(&self) -> bool {
+        self.session.color.is_some_and(|c| c != self.session.board.turn)
+    }
+
+    // Wether one of our pieces will be on `pos` once the queued premoves have been made
+    fn is_ours_after_premoves(&self, pos: Pos) -> bool {
+        let mut ours = match &self.session.board.board[pos.y as usize][pos.x as usize] {
+            Some(p) => Some(p.get_color()) == self.session.color,
+            None => false,
+        };
+        for &(from, to) in &self.premoves {
+            if to == pos {
+                ours = true;
+            } else if from == pos {
+                ours = false;
+            }
+        }
+        ours
+    }
+
+    // The first click picks a piece and the second where it goes. Clicking anywhere else drops
+    // every queued premove.
+    fn premove_click(&mut self, pos: Pos) {
+        match self.premove_from.take() {
+            Some(from) if from != pos => {
+                debug!(target: INPUT, "Queueing premove {}{}", pos_name(from), pos_name(pos));
+                self.premoves.push((from, pos));
+            }
+            Some(_) => (),
+            None if self.is_ours_after_premoves(pos) => self.premove_from = Some(pos),
+            None => self.cancel_premoves(),
+        }
+    }
+
+    fn cancel_premoves(&mut self) {
+        self.premoves.clear();
+        self.premove_from = None;
+    }
+
+    // Makes the first queued premove once it is our turn. If it isn't legal in the position the
+    // opponent left us, none of the others are made either.
+    fn play_premove(&mut self, ctx: &Context, shared: &mut Shared) {
+        if self.premoves.is_empty() || self.is_opponents_turn() || self.outcome.is_some() {
+            return;
+        }
+        let (from, to) = self.premoves.remove(0);
+        let legal = self.session.can_move_piece_at(from) && self.session.board.get_possible_moves_at_square(from).contains(&to);
+        if !legal {
+            debug!(target: INPUT, "Dropping the premoves, {}{} isn't legal anymore", pos_name(from), pos_name(to));
+            self.cancel_premoves();
+            return;
+        }
+        debug!(target: INPUT, "Making premove {}{}", pos_name(from), pos_name(to));
+        let before = self.session.history.len();
+        self.session.make_move(from, to, None);
+        self.state = State::Waiting;
+        if self.session.history.len() > before {
+            self.moved(ctx, shared);
+        }
+    }
+
+    // Releasing the right mouse button on the square it went down on circles the square, anywhere
+    // else on the board draws an arrow
+    fn annotate(&mut self, ctx: &Context, shared: &Shared, to: Pos) {
+        let from = match self.drawing_from.take() {
+            Some(from) => from,
+            None => return,
+        };
+        let mods = ctx.keyboard.active_mods();
+        let shift = mods.contains(KeyMods::SHIFT) || mods.contains(KeyMods::CTRL);
+        let color = annotations::color_for(shift, mods.contains(KeyMods::ALT));
+        debug!(target: INPUT, "Annotating {}{} in {:?}", pos_name(from), pos_name(to), color);
+        annotations::toggle(&mut self.annotations, Annotation { from, to, color });
+        self.share_annotations(shared);
+    }
+
+    // A left click wipes the board clean, for whoever we share our annotations with too
+    fn clear_annotations(&mut self, shared: &Shared) {
+        self.peer_annotations.clear();
+        if !self.annotations.is_empty() {
+            self.annotations.clear();
+            self.share_annotations(shared);
+        }
+    }
+
+    fn share_annotations(&mut self, shared: &Shared) {
+        if shared.settings.share_annotations {
+            self.session.share_annotations(&self.annotations);
+        }
+    }
+
+    // Everything drawn on the board, which is also what a screenshot shows
+    fn draw_board_layers(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared) {
+        let premoves: Vec<Pos> = self.premoves.iter().flat_map(|&(from, to)| [from, to]).chain(self.premove_from).collect();
+        // Our own annotations are drawn over the ones shared with us
+        let annotations: Vec<Annotation> = self.peer_annotations.iter().chain(&self.annotations).copied().collect();
+        let shown = BoardView {
+            selected: self.selected_pos,
+            highlights: &self.highlights,
+            marked: &premoves,
+            annotations: &annotations,
+            ..view(shared, &self.session.board, self.is_flipped(shared))
+        };
+        draw_view(ctx, canvas, shared, &shown);
+    }
+
+    // Saves the board as it is shown (F6) or the whole game so far (F7)
+    fn export(&self, ctx: &mut Context, shared: &mut Shared, whole_game: bool) {
+        let size = shared.settings.export_size;
+        let result = if whole_game {
+            let path = export_path("gif");
+            self.session
+                .pgn()
+                .boards()
+                .map_err(GameError::CustomError)
+                .and_then(|boards| save_gif(ctx, shared, &boards, self.is_flipped(shared), size, shared.settings.gif_delay, &path))
+                .map(|()| path)
+        } else {
+            let path = export_path("png");
+            save_png(ctx, size, &path, |ctx, canvas| self.draw_board_layers(ctx, canvas, shared)).map(|()| path)
+        };
+        report(shared, result);
+    }
+
+    // The players at the top of the side panel, black above white like on the board. The side to
+    // move is marked.
+    fn draw_players(&self, canvas: &mut Canvas) {
+        let left = SCREEN_DIMENSIONS.0 as f32 + 12.0;
+        for (i, color) in [chess::util::Color::Black, chess::util::Color::White].into_iter().enumerate() {
+            let marker = if self.state == State::Playing && self.session.board.turn == color { ">" } else { " " };
+            let side = if color == chess::util::Color::White { "White" } else { "Black" };
+            let name = match self.session.player_name(color) {
+                Some(name) => name,
+                None if self.engine.is_some() && self.session.color != Some(color) => "Engine",
+                None => "Waiting for a player",
+            };
+            let text = graphics::Text::new(format!("{} {}: {}", marker, side, name));
+            let dst = Vec2::new(left, 12.0 + i as f32 * 28.0);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::WHITE));
+        }
+        if let Some(opening) = &self.opening {
+            let text = graphics::Text::new(opening.as_str());
+            canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(left, 68.0)).color(Color::from_rgb(180, 180, 180)));
+        }
+    }
+}
+
+impl Scene for GameScreen {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> Transition {
+        let mut events = match &mut self.replay {
+            Some(replay) => replay.step(&mut self.session),
+            None => Vec::new(),
+        };
+        events.extend(self.session.poll());
+        for event in events {
+            match event {
+                Event::Connected { success } => {
+                    if success {
+                        shared.message = None;
+                        shared.announce("Connected");
+                        self.connected();
+                    } else {
+                        info!(target: NETWORKING, "Host refused the connection");
+                    }
+                },
+                Event::Error(e) => {
+                    shared.announce(&e);
+                    shared.message = Some(e);
+                }
+                Event::Chat(m) => {
+                    if !self.chat.muted || m.system {
+                        shared.announce(&if m.system { m.text.clone() } else { format!("{} says {}", m.sender, m.text) });
+                    }
+                    self.chat.push(m, false);
+                }
+                Event::Disconnected => {
+                    shared.announce("Connection lost");
+                    self.chat.push(system_message("Connection lost", self.session.chat_channel()), false);
+                },
+                Event::Moved => {
+                    self.state = State::Playing;
+                    self.moved(ctx, shared);
+                }
+                Event::MoveRejected => {
+                    shared.announce("The move was rejected");
+                    self.state = State::Playing;
+                    self.cancel_premoves();
+                    self.move_entry.refresh(&self.session.board);
+                }
+                Event::Annotations(a) => self.peer_annotations = a,
+                Event::GameList(_) => (),
+            }
+        }
+        if !self.started && self.has_started() {
+            self.started = true;
+            shared.play(ctx, Sound::GameStart);
+            shared.announce(match self.session.color {
+                Some(chess::util::Color::White) => "The game has started, you play white",
+                Some(chess::util::Color::Black) => "The game has started, you play black",
+                None => "The game has started",
+            });
+        }
+        self.play_premove(ctx, shared);
+        self.update_engine(ctx, shared);
+        self.play_premove(ctx, shared);
+        Transition::Stay
+    }
+
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared) {
+        self.draw_board_layers(ctx, canvas, shared);
+        let entry = &self.move_entry;
+        if self.is_typing_move() && (self.typing_move || !entry.input.is_empty() || entry.error.is_some()) {
+            entry.draw(ctx, canvas);
+        }
+
+        // draw the side panel
+        let title = match self.session.chat_channel() {
+            ChatChannel::Players => "Chat",
+            ChatChannel::Spectators => "Spectator chat",
+        };
+        self.chat.draw(ctx, canvas, title, self.chat_enabled());
+        self.draw_players(canvas);
+
+        // draw the spectator count
+        if self.session.spectator_count() > 0 {
+            let text = graphics::Text::new(format!("Spectators: {}", self.session.spectator_count()));
+            canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(8.0, 8.0)).color(Color::BLACK));
+        }
+
+        if let Some(outcome) = self.outcome {
+            let text = graphics::Text::new(format!("{}. F3: review the game, F7: save it as a GIF, Escape: back to the menu", outcome.describe()));
+            let dst = Vec2::new(8.0, SCREEN_DIMENSIONS.1 as f32 / 2.0);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::RED));
+        }
+    }
+
+    fn mouse_down(&mut self, ctx: &mut Context, shared: &mut Shared, button: MouseButton, x: f32, y: f32) -> Transition {
+        if self.chat.is_on_mute_button(x, y) {
+            self.chat.muted = !self.chat.muted;
+            return Transition::Stay;
+        }
+        // Everyone may draw on the board, even spectators and during a replay
+        match button {
+            MouseButton::Right => {
+                self.drawing_from = square_at(x, y).map(|pos| flip(pos, self.is_flipped(shared)));
+                return Transition::Stay;
+            }
+            MouseButton::Left => self.clear_annotations(shared),
+            _ => return Transition::Stay,
+        }
+        // Spectators only get to watch, and so does everyone watching a replay or a finished game
+        if self.session.is_spectator || self.replay.is_some() || self.outcome.is_some() {
+            return Transition::Stay;
+        }
+        // Nothing else in the side panel reacts to clicks
+        let pos = match square_at(x, y) {
+            Some(pos) => flip(pos, self.is_flipped(shared)),
+            None => return Transition::Stay,
+        };
+        if self.is_opponents_turn() {
+            self.premove_click(pos);
+            return Transition::Stay;
+        }
+        if let Some(p) = self.selected_pos {
+            if self.highlights.contains(&pos) {
+                self.play(ctx, shared, p, pos, None);
+                return Transition::Stay;
+            }
+        }
+        self.highlights = self.session.board.get_possible_moves_at_square(pos);
+        self.selected_pos = None;
+        trace!(target: RENDERING, "Board after the click:\n{}", self.session.board.print(None));
+        if self.session.can_move_piece_at(pos) {
+            self.selected_pos = Some(pos);
+        }
+        Transition::Stay
+    }
+
+    fn mouse_up(&mut self, ctx: &mut Context, shared: &mut Shared, button: MouseButton, x: f32, y: f32) -> Transition {
+        if button != MouseButton::Right {
+            return Transition::Stay;
+        }
+        match square_at(x, y) {
+            Some(pos) => self.annotate(ctx, shared, flip(pos, self.is_flipped(shared))),
+            // Letting go over the side panel draws nothing
+            None => self.drawing_from = None,
+        }
+        Transition::Stay
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, keycode: KeyCode) -> Transition {
+        match keycode {
+            // A half typed move is thrown away first
+            KeyCode::Escape if self.is_typing_move() && !self.move_entry.input.is_empty() => {
+                self.move_entry.clear();
+                return Transition::Stay;
+            }
+            // Leaving the game hangs up on everyone in it
+            KeyCode::Escape => return Transition::Pop,
+            KeyCode::F3 => match ReviewScreen::new(self.session.pgn()) {
+                Ok(review) => return Transition::Push(Box::new(review)),
+                Err(e) => shared.message = Some(e),
+            },
+            // The board can't be seen by everyone, this describes it
+            KeyCode::F5 => {
+                shared.announce(&accessibility::describe_position(&self.session.board));
+                return Transition::Stay;
+            }
+            KeyCode::F6 | KeyCode::F7 => {
+                self.export(ctx, shared, keycode == KeyCode::F7);
+                return Transition::Stay;
+            }
+            KeyCode::F4 => {
+                self.typing_move = !self.typing_move;
+                return Transition::Stay;
+            }
+            _ => (),
+        }
+        if self.is_typing_move() {
+            match keycode {
+                KeyCode::Back => self.move_entry.pop(&self.session.board),
+                KeyCode::Tab => self.move_entry.complete(&self.session.board),
+                KeyCode::Return if !self.move_entry.input.is_empty() => self.submit_move(ctx, shared),
+                _ => (),
+            }
+        } else if self.chat_enabled() {
+            match keycode {
+                KeyCode::Back => {
+                    self.chat.input.pop();
+                }
+                KeyCode::Return if !self.chat.input.is_empty() => {
+                    let text = std::mem::take(&mut self.chat.input);
+                    if let Some(m) = self.session.send_chat(text) {
+                        self.chat.push(m, true);
+                    }
+                }
+                _ => (),
+            }
+        }
+        Transition::Stay
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) -> Transition {
+        if self.is_typing_move() {
+            // Spaces would only get in the way of reading the move
+            if !character.is_whitespace() {
+                self.move_entry.push(character, &self.session.board);
+            }
+        } else if self.chat_enabled() {
+            self.chat.input.push(character);
+        }
+        Transition::Stay
+    }
+}
@@ -0,0 +1,32 @@
+use crate::networking::{Capability, ProtocolVersion, PROTOCOL_VERSION};
+
+// The optional features this build implements
+pub const SUPPORTED_CAPABILITIES: [Capability; 4] = [Capability::Spectate, Capability::Lobby, Capability::Chat, Capability::Annotations];
+
+pub fn supported_capabilities() -> Vec<i32> {
+    SUPPORTED_CAPABILITIES.iter().map(|&c| c as i32).collect()
+}
+
+// Checks the protocol version a peer sent in its handshake
+pub fn check_version(version: i32) -> Result<(), String> {
+    // Peers from before versioning don't send anything and speak the first version
+    let version = match ProtocolVersion::from_i32(version) {
+        Some(ProtocolVersion::Unspecified) => ProtocolVersion::V1,
+        Some(v) => v,
+        None => return Err(format!("Peer speaks unknown protocol version {}, we speak {}", version, PROTOCOL_VERSION.as_str_name())),
+    };
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(format!("Peer speaks protocol version {}, we speak {}", version.as_str_name(), PROTOCOL_VERSION.as_str_name()))
+    }
+}
+
+// The capabilities both we and the peer support. Values we don't know about are ignored.
+pub fn negotiate(peer: &[i32]) -> Vec<Capability> {
+    SUPPORTED_CAPABILITIES
+        .iter()
+        .copied()
+        .filter(|&c| peer.contains(&(c as i32)))
+        .collect()
+}
@@ -0,0 +1,82 @@
+use crate::networking::PlayerIdentity;
+use std::path::{Path, PathBuf};
+
+// The name of peers that don't tell us who they are
+pub const ANONYMOUS: &str = "Anonymous";
+// Longer names are cut so they fit in the side panel
+const MAX_NAME_LENGTH: usize = 24;
+const TOKEN_LENGTH: usize = 32;
+
+// Where our token is kept, next to the TLS certificate
+pub fn default_token_path() -> PathBuf {
+    crate::tls::default_dir().join("player_token")
+}
+
+// Who we are to the host, with the token kept in the default place
+pub fn load(name: String, password: Option<String>) -> std::io::Result<PlayerIdentity> {
+    Ok(PlayerIdentity {
+        name,
+        token: load_or_generate_token(&default_token_path())?,
+        password,
+    })
+}
+
+// Loads our token from `path`, on first run a new one is generated and saved there
+pub fn load_or_generate_token(path: &Path) -> std::io::Result<Vec<u8>> {
+    if let Ok(token) = std::fs::read(path) {
+        if token.len() == TOKEN_LENGTH {
+            return Ok(token);
+        }
+    }
+    let mut token = vec![0_u8; TOKEN_LENGTH];
+    getrandom::getrandom(&mut token).map_err(|e| std::io::Error::other(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, &token)?;
+    Ok(token)
+}
+
+pub fn name_of(identity: &Option<PlayerIdentity>) -> String {
+    match identity {
+        Some(i) if !i.name.trim().is_empty() => i.name.trim().chars().take(MAX_NAME_LENGTH).collect(),
+        _ => ANONYMOUS.to_string(),
+    }
+}
+
+// Peers without a token can't prove they are the same player as before
+pub fn same_player(a: &PlayerIdentity, b: &Option<PlayerIdentity>) -> bool {
+    match b {
+        Some(b) => !a.token.is_empty() && a.token == b.token,
+        None => false,
+    }
+}
+
+// Checks the password someone joining a game gave us
+pub fn check_password(password: &Option<String>, identity: &Option<PlayerIdentity>) -> Result<(), String> {
+    match password {
+        Some(p) if identity.as_ref().and_then(|i| i.password.as_ref()) != Some(p) => {
+            Err("Wrong game password".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Picks the seat for a player. `seats` holds who took each seat before and wether that player is
+// still connected. A seat someone left can only be taken back by the same player, otherwise the
+// first seat that was never taken is used.
+pub fn pick_seat(seats: &[(&Option<PlayerIdentity>, bool)], identity: &Option<PlayerIdentity>) -> Result<usize, String> {
+    let returning = seats
+        .iter()
+        .position(|(player, connected)| !connected && player.as_ref().is_some_and(|p| same_player(p, identity)));
+    if let Some(seat) = returning {
+        return Ok(seat);
+    }
+    if let Some(seat) = seats.iter().position(|(player, _)| player.is_none()) {
+        return Ok(seat);
+    }
+    match seats.iter().find(|(_, connected)| !connected) {
+        Some((player, _)) => Err(format!("Only {} can take the empty seat", name_of(player))),
+        None => Err("The game already has two players".to_string()),
+    }
+}
@@ -0,0 +1,29 @@
+// Everything that is shared between the GUI and the game server. Nothing in here may depend on
+// ggez since the server is built without it.
+pub mod accessibility;
+pub mod annotations;
+pub mod engine;
+pub mod error;
+pub mod handshake;
+pub mod identity;
+pub mod logging;
+pub mod networking;
+pub mod notation;
+pub mod openings;
+pub mod pgn;
+pub mod polyglot;
+pub mod puzzles;
+// Drawing the board without ggez, for the tests
+#[cfg(feature = "headless")]
+pub mod raster;
+pub mod recording;
+pub mod render;
+pub mod rules;
+pub mod server;
+pub mod session;
+pub mod settings;
+pub mod sound;
+pub mod stream;
+pub mod tls;
+pub mod transport;
+pub mod websocket;
@@ -0,0 +1,183 @@
+use chess_gui::logging::NETWORKING;
+use chess_gui::networking::{ColorPreference, GameInfo, TimeControl};
+use chess_gui::session::{Event, Session};
+use chess_gui::settings;
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+use log::info;
+
+use crate::game_screen::GameScreen;
+use crate::scene::{Scene, Shared, Transition};
+use crate::utils::*;
+
+const ROW_HEIGHT: f32 = 48.0;
+// The list of games starts below the title and instructions
+const LIST_TOP: f32 = 160.0;
+
+// (initial minutes, increment seconds) the player can cycle through when creating a game
+pub const TIME_CONTROLS: [(u32, u32); 4] = [(5, 0), (10, 0), (10, 5), (30, 0)];
+
+// The time control after `tc` in TIME_CONTROLS. One that isn't in the list, e.g. from the settings
+// file, is followed by the first.
+pub fn next_time_control(tc: settings::TimeControl) -> settings::TimeControl {
+    let next = TIME_CONTROLS
+        .iter()
+        .position(|&t| t == (tc.minutes, tc.increment))
+        .map_or(0, |i| (i + 1) % TIME_CONTROLS.len());
+    let (minutes, increment) = TIME_CONTROLS[next];
+    settings::TimeControl { minutes, increment }
+}
+
+pub struct Lobby {
+    pub games: Vec<GameInfo>,
+    pub player_name: String,
+    time_control: settings::TimeControl,
+}
+
+impl Lobby {
+    // Games are created with the time control from the settings until the player picks another
+    pub fn new(player_name: String, time_control: settings::TimeControl) -> Lobby {
+        Lobby {
+            games: Vec::new(),
+            player_name,
+            time_control,
+        }
+    }
+
+    pub fn next_time_control(&mut self) {
+        self.time_control = next_time_control(self.time_control);
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control.to_proto()
+    }
+
+    // The game shown at the given height of the screen
+    pub fn game_at(&self, y: f32) -> Option<&GameInfo> {
+        if y < LIST_TOP {
+            return None;
+        }
+        self.games.get(((y - LIST_TOP) / ROW_HEIGHT) as usize)
+    }
+
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut Canvas) {
+        let title = graphics::Text::new(format!("Lobby - playing as {}", self.player_name));
+        canvas.draw(&title, graphics::DrawParam::new().dest(Vec2::new(16.0, 16.0)).color(Color::WHITE));
+
+        let help = graphics::Text::new(format!(
+            "Click a game to join it. R: refresh, T: time control ({}), C/W/B: create as random/white/black, Escape: leave",
+            format_time_control(&Some(self.time_control()))
+        ));
+        canvas.draw(&help, graphics::DrawParam::new().dest(Vec2::new(16.0, 64.0)).color(Color::WHITE));
+
+        if self.games.is_empty() {
+            let text = graphics::Text::new("No open games");
+            canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(16.0, LIST_TOP)).color(Color::WHITE));
+            return;
+        }
+
+        let mut mb = MeshBuilder::new();
+        for (i, _) in self.games.iter().enumerate().filter(|(i, _)| i % 2 == 0) {
+            mb.rectangle(
+                DrawMode::fill(),
+                graphics::Rect { x: 0.0, y: LIST_TOP + i as f32 * ROW_HEIGHT, w: SCREEN_DIMENSIONS.0 as f32, h: ROW_HEIGHT },
+                Color::from_rgb(40, 60, 80)).expect("Error in building mesh");
+        }
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        for (i, game) in self.games.iter().enumerate() {
+            let preference = ColorPreference::from_i32(game.color_preference).unwrap_or(ColorPreference::Random);
+            let text = graphics::Text::new(format!(
+                "#{}  {}  {}  creator plays {}{}",
+                game.game_id,
+                game.creator_name,
+                format_time_control(&game.time_control),
+                preference.as_str_name().to_lowercase(),
+                if game.has_password { "  (password)" } else { "" }
+            ));
+            let dst = Vec2::new(16.0, LIST_TOP + i as f32 * ROW_HEIGHT + ROW_HEIGHT / 3.0);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::WHITE));
+        }
+    }
+}
+
+// The lobby of a game server, left for the game once the server has put us in one
+pub struct LobbyScreen {
+    lobby: Lobby,
+    session: Session,
+}
+
+impl LobbyScreen {
+    pub fn new(lobby: Lobby, session: Session) -> LobbyScreen {
+        LobbyScreen { lobby, session }
+    }
+}
+
+impl Scene for LobbyScreen {
+    fn update(&mut self, _ctx: &mut Context, shared: &mut Shared) -> Transition {
+        for event in self.session.poll() {
+            match event {
+                Event::Connected { success: true } => {
+                    shared.message = None;
+                    let session = std::mem::take(&mut self.session);
+                    return Transition::Replace(Box::new(GameScreen::joined(session)));
+                }
+                // Someone else got the game first, show what is still open
+                Event::Connected { success: false } => self.session.list_games(),
+                Event::GameList(games) => self.lobby.games = games,
+                Event::Error(e) => shared.message = Some(e),
+                Event::Disconnected => {
+                    info!(target: NETWORKING, "Lost the connection to the game server");
+                    shared.message = Some("Lost the connection to the game server".to_string());
+                    return Transition::Pop;
+                }
+                Event::Chat(_) | Event::Moved | Event::MoveRejected | Event::Annotations(_) => (),
+            }
+        }
+        Transition::Stay
+    }
+
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, _shared: &Shared) {
+        self.lobby.draw(ctx, canvas);
+    }
+
+    fn mouse_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, _button: MouseButton, _x: f32, y: f32) -> Transition {
+        if let Some(game) = self.lobby.game_at(y) {
+            self.session.join_game(game.game_id);
+        }
+        Transition::Stay
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, keycode: KeyCode) -> Transition {
+        let preference = match keycode {
+            KeyCode::Escape => return Transition::Pop,
+            KeyCode::R => {
+                self.session.list_games();
+                None
+            }
+            KeyCode::T => {
+                self.lobby.next_time_control();
+                None
+            }
+            KeyCode::C => Some(ColorPreference::Random),
+            KeyCode::W => Some(ColorPreference::White),
+            KeyCode::B => Some(ColorPreference::Black),
+            _ => None,
+        };
+        if let Some(preference) = preference {
+            let time_control = self.lobby.time_control();
+            self.session.create_game(time_control, preference);
+        }
+        Transition::Stay
+    }
+}
+
+fn format_time_control(tc: &Option<TimeControl>) -> String {
+    match tc {
+        Some(tc) => format!("{}+{}", tc.initial_seconds / 60, tc.increment_seconds),
+        None => "untimed".to_string(),
+    }
+}
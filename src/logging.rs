@@ -0,0 +1,147 @@
+use crate::settings::take_option;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// Targets for the parts of the program that log the most, e.g.
+// `log::debug!(target: NETWORKING, ...)`. Everything else logs under its module path.
+pub const NETWORKING: &str = "networking";
+pub const INPUT: &str = "input";
+pub const RENDERING: &str = "rendering";
+
+// The filter is read from this variable unless --log is given, e.g. "info,networking=debug"
+pub const ENV_VAR: &str = "CHESS_LOG";
+const DEFAULT_FILTER: &str = "info";
+
+// Which game we are in and as what, added to every record
+struct Context {
+    role: Option<&'static str>,
+    game_id: Option<u64>,
+}
+
+static CONTEXT: Mutex<Context> = Mutex::new(Context { role: None, game_id: None });
+
+pub fn set_role(role: &'static str) {
+    CONTEXT.lock().unwrap().role = Some(role);
+}
+
+pub fn set_game_id(game_id: Option<u64>) {
+    CONTEXT.lock().unwrap().game_id = game_id;
+}
+
+// A default level followed by levels for single targets, e.g. "warn,networking=trace,input=off".
+// A target also covers everything below it, "chess_gui" covers "chess_gui::session".
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let mut filter = Filter { default: LevelFilter::Info, targets: Vec::new() };
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |level: &str| {
+                level
+                    .parse::<LevelFilter>()
+                    .map_err(|_| format!("Unknown log level '{}'", level))
+            };
+            match directive.split_once('=') {
+                Some((target, level)) => filter.targets.push((target.trim().to_string(), parse_level(level.trim())?)),
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+        Ok(filter)
+    }
+
+    // The most specific target wins
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .filter(|(t, _)| target == t || target.strip_prefix(t.as_str()).is_some_and(|rest| rest.starts_with("::")))
+            .max_by_key(|(t, _)| t.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|&(_, l)| l).fold(self.default, std::cmp::max)
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    file: Option<Mutex<std::fs::File>>,
+    start: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let context = {
+            let c = CONTEXT.lock().unwrap();
+            let game = c.game_id.map_or(String::new(), |id| format!(" game={}", id));
+            format!("{}{}", c.role.unwrap_or("-"), game)
+        };
+        let line = format!(
+            "{:>9.3} {:<5} [{}] {}: {}",
+            self.start.elapsed().as_secs_f32(),
+            record.level(),
+            record.target(),
+            context,
+            record.args()
+        );
+        match &self.file {
+            Some(file) => {
+                let _ = writeln!(file.lock().unwrap(), "{}", line);
+                // Problems are still worth seeing while the game runs
+                if record.level() <= Level::Warn {
+                    eprintln!("{}", line);
+                }
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+// Removes `--log filter` and `--log-file path` from the arguments and returns them
+pub fn take_options(args: &mut Vec<String>) -> (Option<String>, Option<PathBuf>) {
+    let filter = take_option(args, "--log");
+    let file = take_option(args, "--log-file").map(PathBuf::from);
+    (filter, file)
+}
+
+// Installs the logger. Without a filter the one in CHESS_LOG is used, or "info" if that isn't set
+// either. With a file everything is written to it and only warnings and errors go to stderr.
+pub fn init(filter: Option<&str>, file: Option<&Path>) -> Result<(), String> {
+    let spec = match filter {
+        Some(f) => f.to_string(),
+        None => std::env::var(ENV_VAR).unwrap_or_else(|_| DEFAULT_FILTER.to_string()),
+    };
+    let filter = Filter::parse(&spec)?;
+    let file = match file {
+        Some(path) => Some(Mutex::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?,
+        )),
+        None => None,
+    };
+    log::set_max_level(filter.max_level());
+    log::set_boxed_logger(Box::new(Logger { filter, file, start: Instant::now() })).map_err(|e| e.to_string())
+}
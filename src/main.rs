@@ -1,197 +1,97 @@
 use ggez::{
-    event::{self, EventHandler},
-    graphics::{self, Color, MeshBuilder, DrawMode},
+    event,
+    graphics,
     Context, GameResult, conf,
 };
-use glam::*;
-use networking::C2sMessage;
-use networking::S2cMessage;
-use prost::{Message};
-use std::{env, path, io::{Write, Read}};
-use std::net::*;
-mod networking;
+use chess_gui::logging::{self, INPUT};
+use chess_gui::pgn::Pgn;
+use chess_gui::settings::{self, take_option, Settings};
+use log::{trace, warn};
+use std::{env, path};
+mod board_view;
+mod chat;
+mod connecting;
+mod export;
+mod game_screen;
+mod lobby;
+mod menu;
+mod move_entry;
+mod puzzle_screen;
+mod review;
+mod scene;
+mod settings_screen;
+mod sounds;
 mod utils;
+use connecting::ConnectingScreen;
+use menu::Menu;
+use review::ReviewScreen;
+use scene::{Scene, Shared, Transition};
+use settings_screen::SettingsScreen;
 use utils::*;
 
-fn get_stream() -> (TcpStream, bool) {
-    // A stream and a boolean indicating wether or not the program is a host or a client
-    let (stream, is_client) = {
-        let mut args = std::env::args();
-        // Skip path to program
-        let _ = args.next();
-
-        // Get first argument after path to program
-        let host_or_client = args
-            .next()
-            .expect("Expected arguments: host or client 'ip'");
-
-        match host_or_client.as_str() {
-            // If the program is running as host we listen on port 8080 until we get a
-            // connection then we return the stream.
-            "host" => {
-                let listener = TcpListener::bind("127.0.0.1:1337").unwrap();
-                (listener.incoming().next().unwrap().unwrap(), false)
-            }
-            // If the program is running as a client we connect to the specified IP address and
-            // return the stream.
-            "client" => {
-                let ip = args.next().expect("Expected ip address after client");
-                let stream = TcpStream::connect(ip).expect("Failed to connect to host");
-                (stream, true)
-            }
-            // Only --host and --client are valid arguments
-            _ => panic!("Unknown command: {}", host_or_client),
-        }
-    };
-
-     //Set TcpStream to non blocking so that we can do networking in the update thread
-    stream
-        .set_nonblocking(true)
-        .expect("Failed to set stream to non blocking");
-    (stream, is_client)
+// Keeps the screens the player has gone through, the one on top is the one shown. The menu is
+// always at the bottom.
+struct App {
+    shared: Shared,
+    scenes: Vec<Box<dyn Scene>>,
 }
 
-
-struct MainState {
-    pieces: Imglib,
-    board: chess::board::Board, 
-    highlights: Vec<chess::util::Pos>,
-    selected_pos: Option<chess::util::Pos>, 
-    stream: Option<TcpStream>, 
-    is_client: bool, 
-    state: State
-}
-
-impl MainState {
-    fn new(ctx: &mut Context) -> GameResult<MainState> {
-        let mut s = MainState { 
-            pieces: Imglib::new(ctx)?,
-            board: chess::board::Board::new(), 
-            highlights: Vec::new(), 
-            selected_pos: None, 
-            stream: None, 
-            is_client: false, 
-            state: State::Waiting
-        };
-
-        s.draw(ctx);
-        Ok(s)
-    }
-
-    
-
-    
-
-    fn send_c2s_packet(&mut self, data: networking::C2sMessage) {
-        let v = data.encode_to_vec(); //(&mut buf).expect("Couldn't encode message");
-        self.stream.as_ref().unwrap().write(&v).expect("Failed to send c2s packet");
-    }
-
-    fn send_s2c_packet(&mut self, data: networking::S2cMessage) {
-        let v = data.encode_to_vec(); //(&mut buf).expect("Couldn't encode message");
-        self.stream.as_ref().unwrap().write(&v).expect("Failed to send c2s packet");
+impl App {
+    fn new(ctx: &mut Context, settings: Settings, options: Vec<String>) -> GameResult<App> {
+        Ok(App {
+            shared: Shared {
+                pieces: Imglib::new(ctx, &settings.piece_set)?,
+                sounds: sounds::Sounds::new(ctx, &settings.sound_pack),
+                settings,
+                message: None,
+                options,
+            },
+            scenes: vec![Box::new(Menu::new())],
+        })
     }
 
-    fn receive_c2s_packet(&mut self) -> Option<networking::C2sMessage> {
-        let mut buf= [0_u8; 512];
-        if self.stream.is_none() { return None }
-        match self.stream.as_ref().unwrap().read(&mut buf) {
-            Ok(p) => {
-                println!("Recieved s2c packet {}", p);
-                return Some(C2sMessage::decode(&buf[..p], ).expect("erro reading"))
-            },
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock => return None,
-                _ => panic!("Error: {}", e)
+    fn apply(&mut self, transition: Transition) {
+        match transition {
+            Transition::Stay => (),
+            Transition::Push(scene) => self.scenes.push(scene),
+            Transition::Replace(scene) => {
+                self.pop();
+                self.scenes.push(scene);
             }
+            Transition::Pop => self.pop(),
         }
     }
 
-    fn receive_s2c_packet(&mut self) -> Option<networking::S2cMessage> {
-        let mut buf= [0_u8; 512];
-        if self.stream.is_none() { return None }
-        match self.stream.as_ref().unwrap().read(&mut buf) {
-            Ok(p) => {
-                println!("Recieved s2c packet {}", p);
-                return Some(S2cMessage::decode(&buf[..p], ).expect("erro reading"))
-            } ,
-            Err(e) => match e.kind() {
-                std::io::ErrorKind::WouldBlock => return None,
-                _ => panic!("Error: {}", e)
-            }
+    fn pop(&mut self) {
+        if self.scenes.len() > 1 {
+            self.scenes.pop();
         }
-        
     }
-}
-
-impl event::EventHandler<ggez::GameError> for MainState {
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
 
-        if self.is_client {
-            let data = self.receive_s2c_packet();
-            if data.is_some() {
-                match data.unwrap().msg {
-                    None => println!("Received NONE msg!!!"), 
-                    Some(msg) => {
-                        println!("Received SOME msg!!!!");
-                        match msg {
-                            networking::s2c_message::Msg::Move(m) => {
-                                let p = chess::util::Pos {
-                                    x: (m.from_square % 8) as i8,
-                                    y: (m.from_square / 8) as i8,
-                                };
-                                let pos = chess::util::Pos {
-                                    x: (m.to_square % 8) as i8,
-                                    y: (m.to_square / 8) as i8,
-                                };
-                                self.board.perform_move(p, pos, None);
-                                println!("RECEIVED MOVE PACKET");
-                                self.draw(ctx);
-                            },
-                            networking::s2c_message::Msg::ConnectAck(ca) => {
-                                println!("RECEIVED COnnecte Ack PACKET");
-    
-                            },
-                            networking::s2c_message::Msg::MoveAck(ma) => {
-                                println!("RECEIVED move Ack PACKET");
-    
-                            },
-                        }
-                    }
+    // Starts what the command line asks for on top of the menu, e.g. `host` or `pgn game.pgn`
+    fn start(&mut self, mut args: Vec<String>) {
+        match args.get(1).map(String::as_str) {
+            None => (),
+            Some("pgn") => {
+                let file = args.get(2).cloned().unwrap_or_default();
+                match Pgn::load(path::Path::new(&file)).and_then(ReviewScreen::new) {
+                    Ok(review) => self.scenes.push(Box::new(review)),
+                    Err(e) => self.shared.message = Some(e),
                 }
             }
-            
-        } else {
-            let data = self.receive_c2s_packet();
-            if data.is_some() {
-                match data.unwrap().msg {
-                    None => (), 
-                    Some(msg) => {
-                        match msg {
-                            networking::c2s_message::Msg::Move(m) => {
-                                let p = chess::util::Pos {
-                                    x: (m.from_square % 8) as i8,
-                                    y: (m.from_square / 8) as i8,
-                                };
-                                let pos = chess::util::Pos {
-                                    x: (m.to_square % 8) as i8,
-                                    y: (m.to_square / 8) as i8,
-                                };
-                                self.board.perform_move(p, pos, None);
-                                println!("RECEIVED MOVE PACKET");
-                                self.draw(ctx);
-                            },
-                            networking::c2s_message::Msg::ConnectRequest(_) => {
-                                println!("RECEIVED connect request PACKET");
-    
-                            },
-                        }
-                    }
-                }
+            Some(command) if connecting::COMMANDS.contains(&command) => {
+                args.extend(self.shared.options.iter().cloned());
+                self.scenes.push(Box::new(ConnectingScreen::new(args, &self.shared.settings)));
             }
+            Some(command) => self.shared.message = Some(format!("Unknown command: {}", command)),
         }
-        self.draw(ctx);
+    }
+}
 
+impl event::EventHandler<ggez::GameError> for App {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        let transition = self.scenes.last_mut().expect("The menu is never left").update(ctx, &mut self.shared);
+        self.apply(transition);
         Ok(())
     }
 
@@ -200,160 +100,36 @@ impl event::EventHandler<ggez::GameError> for MainState {
             ctx,
             graphics::CanvasLoadOp::Clear([0.1, 0.2, 0.3, 1.0].into()),
         );
-
-        // draw the grid
-        let mut mb = MeshBuilder::new();
-        for row in 0..GRID_DIMENSIONS.0 {
-            for column in 0..GRID_DIMENSIONS.1 {
-                let mut color: Color = Color::from_rgb(118,150,86);
-                if (row + column) % 2 == 0 { color = Color::from_rgb(238,238,210)}
-                mb.rectangle(
-                    DrawMode::fill(), 
-                    graphics::Rect { x: (row * CELL_DIMENSIONS.0) as f32, y: (column * CELL_DIMENSIONS.1) as f32, w: CELL_DIMENSIONS.0 as f32, h: CELL_DIMENSIONS.1 as f32 }, 
-                    color).expect("Error in building mesh");
-            }
+        if let Some(scene) = self.scenes.last() {
+            scene.draw(ctx, &mut canvas, &self.shared);
         }
-        
-        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new().image_scale(false));
-        
-        //draw selected piece
-        if let Some(h) = self.selected_pos {
-            let mut mb = MeshBuilder::new();
-            mb.rectangle(
-                DrawMode::fill(), 
-                graphics::Rect { x: (h.x as i16 * CELL_DIMENSIONS.0) as f32, y: (h.y as i16 * CELL_DIMENSIONS.1) as f32, w: CELL_DIMENSIONS.0 as f32, h: CELL_DIMENSIONS.1 as f32 }, 
-                Color::from_rgb(0, 85, 71)).expect("Error in building mesh");
-            canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new().image_scale(false));
-            
+        self.shared.draw_message(&mut canvas);
+        canvas.finish(ctx)
     }
-        
-        // draw the pieces
-        for row in 0..GRID_DIMENSIONS.0 {
-            for column in 0..GRID_DIMENSIONS.1 {
-                if let Some(p) = &self.board.board[column as usize][row as usize] {
-                    let t = p.get_type();
-                    let c = p.get_color();
-
-                    let mut img = &self.pieces.white_pawn;
 
-                    if c == chess::util::Color::White {
-                        if t == chess::piece::PieceType::Rook {
-                            img = &self.pieces.white_rook;
-                        } else if t == chess::piece::PieceType::Knight {
-                            img = &self.pieces.white_knight;
-                        } else if t == chess::piece::PieceType::Bishop {
-                            img = &self.pieces.white_bishop;
-                        } else if t == chess::piece::PieceType::Queen {
-                            img = &self.pieces.white_queen;
-                        } else if t == chess::piece::PieceType::King {
-                            img = &self.pieces.white_king;
-                        }
-                    } else {
-                        if t == chess::piece::PieceType::Rook {
-                            img = &self.pieces.black_rook;
-                        } else if t == chess::piece::PieceType::Knight {
-                            img = &self.pieces.black_knight;
-                        } else if t == chess::piece::PieceType::Bishop {
-                            img = &self.pieces.black_bishop;
-                        } else if t == chess::piece::PieceType::Queen {
-                            img = &self.pieces.black_queen;
-                        } else if t == chess::piece::PieceType::King {
-                            img = &self.pieces.black_king;
-                        } else if t == chess::piece::PieceType::Pawn {
-                            img = &self.pieces.black_pawn;
-                        }
-                    }
-
-                    let dst = glam::Vec2::new((row * CELL_DIMENSIONS.0) as f32, (column * CELL_DIMENSIONS.1) as f32);
-                    let scale = glam::Vec2::new((CELL_DIMENSIONS.0 as f32) / (img.width() as f32), (CELL_DIMENSIONS.1 as f32) / (img.height() as f32));
-                    canvas.draw(img, 
-                    graphics::DrawParam::new().dest(dst).scale(scale));
-                }
-            }
-        }
-        
-        //draw highlights
-        let mut mb = MeshBuilder::new();
-        for m in &self.highlights {
-            //mb.rectangle(
-            //    DrawMode::fill(), 
-            //    graphics::Rect { x: (m.x as i16 * CELL_DIMENSIONS.0) as f32, y: (m.y as i16 * CELL_DIMENSIONS.1) as f32, w: CELL_DIMENSIONS.0 as f32, h: CELL_DIMENSIONS.1 as f32 }, 
-            //    Color::CYAN).expect("Error in building mesh");
-            mb.circle(
-                DrawMode::fill(), 
-                Vec2::new((CELL_DIMENSIONS.0 as f32 / 2.0) + (m.x as i16 * CELL_DIMENSIONS.0) as f32, (CELL_DIMENSIONS.1 as f32 / 2.0) + (m.y as i16 * CELL_DIMENSIONS.1) as f32), 
-                CELL_DIMENSIONS.0 as f32 / 10.0, 
-                0.1, 
-                Color::from_rgb(94, 74, 130)).expect("Error in building mesh");
-        }
-        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new().image_scale(false));
-
-        
-        canvas.finish(ctx);
+    fn mouse_button_down_event(
+            &mut self,
+            ctx: &mut Context,
+            button: event::MouseButton,
+            x: f32,
+            y: f32,
+    ) -> Result<(), ggez::GameError> {
+        trace!(target: INPUT, "Clicked at {}, {}", x, y);
+        let transition = self.scenes.last_mut().expect("The menu is never left").mouse_down(ctx, &mut self.shared, button, x, y);
+        self.apply(transition);
         Ok(())
     }
 
-    fn mouse_button_down_event(
+    fn mouse_button_up_event(
             &mut self,
-            _ctx: &mut Context,
-            _button: event::MouseButton,
-            _x: f32,
-            _y: f32,
+            ctx: &mut Context,
+            button: event::MouseButton,
+            x: f32,
+            y: f32,
     ) -> Result<(), ggez::GameError> {
-        println!("Clicked: {}, {}", _x, _y);
-        let pos = chess::util::Pos {
-            x: (_x / CELL_DIMENSIONS.0 as f32) as i8,
-            y: (_y / CELL_DIMENSIONS.0 as f32) as i8,
-        };
-        if let Some(p) = self.selected_pos {
-            println!("here");
-            if self.highlights.contains(&pos) {
-                println!("Moving to pos");
-                let res = self.board.perform_move(p, pos, None);
-                match res {
-                    Ok(r) => println!("OK"), 
-                    Err(e) => println!("Err")
-                }
-                
-                if self.is_client {
-                    let data = C2sMessage {
-                        msg: Some(networking::c2s_message::Msg::Move(networking::Move {
-                            from_square: (p.x + p.y * 8) as u32,
-                            to_square: (pos.x + pos.y * 8) as u32,
-                            promotion: None,
-                        })),
-                    };
-                    println!("SENDING MOVE PACKET CLient");
-                    self.state = State::Waiting;
-                    self.send_c2s_packet(data);
-                } else {
-                    let data = S2cMessage {
-                        msg: Some(networking::s2c_message::Msg::Move(networking::Move {
-                            from_square: (p.x + p.y * 8) as u32,
-                            to_square: (pos.x + pos.y * 8) as u32,
-                            promotion: None,
-                        })),
-                    };
-                    println!("SENDING MOVE PACKET Server");
-                    self.state = State::Waiting;
-                    self.send_s2c_packet(data);
-                }
-
-                self.highlights = Vec::new();
-                self.selected_pos = None;
-                return self.draw(_ctx);
-            }
-        }
-        self.highlights = self.board.get_possible_moves_at_square(pos);
-        self.selected_pos = None;
-        println!("{}", self.board.print(None));
-        if let Some(p) = &self.board.board[pos.y as usize][pos.x as usize] {
-            if p.get_color() == self.board.turn {
-                self.selected_pos = Some(pos);
-            }
-        }
-
-        self.draw(_ctx)
+        let transition = self.scenes.last_mut().expect("The menu is never left").mouse_up(ctx, &mut self.shared, button, x, y);
+        self.apply(transition);
+        Ok(())
     }
 
     fn key_down_event(
@@ -362,16 +138,55 @@ impl event::EventHandler<ggez::GameError> for MainState {
             input: ggez::input::keyboard::KeyInput,
             _repeated: bool,
         ) -> Result<(), ggez::GameError> {
-            if input.keycode.unwrap() == ggez::input::keyboard::KeyCode::Return {
-                let (stream, is_client) = get_stream();
-                self.stream = Some(stream);
-                self.is_client = is_client;
+            trace!(target: INPUT, "Pressed {:?}", input.keycode);
+            // Keys ggez doesn't know only come with a scancode, nothing uses those
+            let keycode = match input.keycode {
+                Some(k) => k,
+                None => return Ok(()),
+            };
+            // The settings can be changed from every screen
+            if keycode == ggez::input::keyboard::KeyCode::F2 && self.scenes.last().is_some_and(|s| s.opens_settings()) {
+                self.scenes.push(Box::new(SettingsScreen::new(self.shared.settings.clone())));
+                return Ok(());
             }
+            let transition = self.scenes.last_mut().expect("The menu is never left").key_down(ctx, &mut self.shared, keycode);
+            self.apply(transition);
+            Ok(())
+    }
 
-            self.draw(ctx)
+    fn text_input_event(&mut self, ctx: &mut Context, character: char) -> Result<(), ggez::GameError> {
+        if character.is_control() {
+            return Ok(());
+        }
+        let transition = self.scenes.last_mut().expect("The menu is never left").text_input(ctx, &mut self.shared, character);
+        self.apply(transition);
+        Ok(())
     }
 }
 pub fn main() -> GameResult {
+    let mut args: Vec<String> = env::args().collect();
+    let (filter, log_file) = logging::take_options(&mut args);
+    logging::init(filter.as_deref(), log_file.as_deref()).map_err(ggez::GameError::CustomError)?;
+    // A broken settings file shouldn't keep the game from starting, it is replaced when the
+    // settings are saved
+    let mut load_error = None;
+    let mut settings = Settings::load(&settings::default_path()).unwrap_or_else(|e| {
+        warn!("{}", e);
+        load_error = Some(e.to_string());
+        Settings::default()
+    });
+    settings.apply_args(&mut args).map_err(|e| ggez::GameError::CustomError(e.to_string()))?;
+    // Connections made from the menu use these too
+    let mut options = Vec::new();
+    if args.iter().any(|a| a == "--tls") {
+        args.retain(|a| a != "--tls");
+        options.push("--tls".to_string());
+    }
+    for flag in ["--password", "--record"] {
+        if let Some(value) = take_option(&mut args, flag) {
+            options.extend([flag.to_string(), value]);
+        }
+    }
 
     let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
         let mut path = path::PathBuf::from(manifest_dir);
@@ -381,14 +196,26 @@ pub fn main() -> GameResult {
         path::PathBuf::from("./resources")
     };
 
-    let cb = ggez::ContextBuilder::new("drawing", "ggez").add_resource_path(resource_dir);
+    let mut cb = ggez::ContextBuilder::new("drawing", "ggez");
+    if let Some(dir) = &settings.resource_dir {
+        cb = cb.add_resource_path(dir);
+    }
+    let cb = cb.add_resource_path(resource_dir);
+
+    // ggez won't start with sound if there is no device to play it on, e.g. on a headless machine,
+    // and its own error doesn't say what to do about it
+    #[cfg(feature = "sound")]
+    if rodio::OutputStream::try_default().is_err() {
+        return Err(ggez::GameError::AudioError("No audio device found, build without the sound feature to play without sound".to_string()));
+    }
 
     let (mut ctx, events_loop) = cb
-    .window_mode(conf::WindowMode::default().dimensions(SCREEN_DIMENSIONS.0 as f32, SCREEN_DIMENSIONS.1 as f32))
+    .window_mode(conf::WindowMode::default().dimensions(WINDOW_DIMENSIONS.0 as f32, WINDOW_DIMENSIONS.1 as f32))
     .build()?;
 
-    let mut state = MainState::new(&mut ctx)?;
+    let mut app = App::new(&mut ctx, settings, options)?;
+    app.shared.message = load_error;
+    app.start(args);
 
-    state.draw(&mut ctx);
-    event::run(ctx, events_loop, state)
+    event::run(ctx, events_loop, app)
 }
@@ -0,0 +1,248 @@
+use chess::util::Color as PieceColor;
+use chess_gui::engine::Engine;
+use chess_gui::pgn::Pgn;
+use chess_gui::polyglot::Book;
+use chess_gui::puzzles::{self, History};
+use chess_gui::settings::Orientation;
+use ggez::event::{self, MouseButton};
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+use std::path::Path;
+
+use crate::board_view::draw_header;
+use crate::connecting::ConnectingScreen;
+use crate::game_screen::GameScreen;
+use crate::puzzle_screen::PuzzleScreen;
+use crate::review::ReviewScreen;
+use crate::scene::{Scene, Shared, Transition};
+use crate::settings_screen::SettingsScreen;
+use crate::utils::*;
+
+const ROW_HEIGHT: f32 = 48.0;
+const LIST_TOP: f32 = 112.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Entry {
+    HotSeat,
+    Computer,
+    Host,
+    Join,
+    Spectate,
+    Lobby,
+    LoadPgn,
+    Puzzles,
+    Settings,
+    Quit,
+}
+
+const ENTRIES: [Entry; 10] = [
+    Entry::HotSeat,
+    Entry::Computer,
+    Entry::Host,
+    Entry::Join,
+    Entry::Spectate,
+    Entry::Lobby,
+    Entry::LoadPgn,
+    Entry::Puzzles,
+    Entry::Settings,
+    Entry::Quit,
+];
+
+impl Entry {
+    fn label(self) -> &'static str {
+        match self {
+            Entry::HotSeat => "Play on this computer",
+            Entry::Computer => "Play against the engine",
+            Entry::Host => "Host a game",
+            Entry::Join => "Join a game at",
+            Entry::Spectate => "Watch a game at",
+            Entry::Lobby => "Find a game on the server at",
+            Entry::LoadPgn => "Review a PGN file",
+            Entry::Puzzles => "Solve puzzles",
+            Entry::Settings => "Settings",
+            Entry::Quit => "Quit",
+        }
+    }
+
+    fn takes_address(self) -> bool {
+        matches!(self, Entry::Join | Entry::Spectate | Entry::Lobby)
+    }
+}
+
+// The first screen, everything else is started from here and comes back to it
+pub struct Menu {
+    selected: usize,
+    // What the player typed, the address from the settings is used while it is empty
+    address: String,
+    pgn_path: String,
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu {
+            selected: 0,
+            address: String::new(),
+            pgn_path: String::new(),
+        }
+    }
+
+    fn entry(&self) -> Entry {
+        ENTRIES[self.selected]
+    }
+
+    fn address(&self, shared: &Shared) -> String {
+        if self.address.is_empty() { shared.settings.address() } else { self.address.clone() }
+    }
+
+    // The text the selected entry lets the player type
+    fn input(&mut self) -> Option<&mut String> {
+        match self.entry() {
+            e if e.takes_address() => Some(&mut self.address),
+            Entry::LoadPgn => Some(&mut self.pgn_path),
+            _ => None,
+        }
+    }
+
+    // Connections are made like the command line would make them, with the options it was given
+    fn connect(&self, shared: &Shared, command: &str) -> Transition {
+        let mut args = vec!["chess-gui".to_string(), command.to_string()];
+        if self.entry().takes_address() {
+            args.push(self.address(shared));
+        }
+        args.extend(shared.options.iter().cloned());
+        Transition::Push(Box::new(ConnectingScreen::new(args, &shared.settings)))
+    }
+
+    fn activate(&mut self, ctx: &mut Context, shared: &mut Shared) -> Transition {
+        shared.message = None;
+        match self.entry() {
+            Entry::HotSeat => Transition::Push(Box::new(GameScreen::hot_seat())),
+            Entry::Computer => {
+                let path = match &shared.settings.engine_path {
+                    Some(path) => path.clone(),
+                    None => {
+                        shared.message = Some("Choose an engine in the settings first".to_string());
+                        return Transition::Stay;
+                    }
+                };
+                // The player takes black if they want to see the board from black's side
+                let color = if shared.settings.orientation == Orientation::Black { PieceColor::Black } else { PieceColor::White };
+                let book = match shared.settings.book_path.as_deref().map(Book::load).transpose() {
+                    Ok(book) => book,
+                    Err(e) => {
+                        shared.message = Some(e);
+                        return Transition::Stay;
+                    }
+                };
+                match Engine::start(&path) {
+                    Ok(engine) => Transition::Push(Box::new(GameScreen::against_engine(engine, color, book))),
+                    Err(e) => {
+                        shared.message = Some(e.to_string());
+                        Transition::Stay
+                    }
+                }
+            }
+            Entry::Host => self.connect(shared, "host"),
+            Entry::Join => self.connect(shared, "client"),
+            Entry::Spectate => self.connect(shared, "spectate"),
+            Entry::Lobby => self.connect(shared, "lobby"),
+            Entry::LoadPgn => match Pgn::load(Path::new(&self.pgn_path)).and_then(ReviewScreen::new) {
+                Ok(review) => Transition::Push(Box::new(review)),
+                Err(e) => {
+                    shared.message = Some(e);
+                    Transition::Stay
+                }
+            },
+            Entry::Puzzles => {
+                let path = match &shared.settings.puzzle_path {
+                    Some(path) => path.clone(),
+                    None => {
+                        shared.message = Some("Choose a puzzle file in the settings first".to_string());
+                        return Transition::Stay;
+                    }
+                };
+                let history_path = puzzles::default_history_path();
+                let screen = puzzles::load_puzzles(&path)
+                    .and_then(|all| Ok((all, History::load(&history_path)?)))
+                    .and_then(|(all, history)| PuzzleScreen::new(all, history, history_path));
+                match screen {
+                    Ok(screen) => {
+                        screen.announce_start(shared);
+                        Transition::Push(Box::new(screen))
+                    }
+                    Err(e) => {
+                        shared.message = Some(e);
+                        Transition::Stay
+                    }
+                }
+            }
+            Entry::Settings => Transition::Push(Box::new(SettingsScreen::new(shared.settings.clone()))),
+            Entry::Quit => {
+                event::request_quit(ctx);
+                Transition::Stay
+            }
+        }
+    }
+}
+
+impl Scene for Menu {
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared) {
+        draw_header(canvas, "Chess", "Up/Down: pick, Return: start, type to change the address or file, F2: settings");
+
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: 0.0, y: LIST_TOP + self.selected as f32 * ROW_HEIGHT, w: SCREEN_DIMENSIONS.0 as f32, h: ROW_HEIGHT },
+            Color::from_rgb(40, 60, 80)).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        for (i, &entry) in ENTRIES.iter().enumerate() {
+            let y = LIST_TOP + i as f32 * ROW_HEIGHT + ROW_HEIGHT / 3.0;
+            let label = graphics::Text::new(entry.label());
+            canvas.draw(&label, graphics::DrawParam::new().dest(Vec2::new(16.0, y)).color(Color::WHITE));
+            let value = match entry {
+                e if e.takes_address() => self.address(shared),
+                Entry::LoadPgn => self.pgn_path.clone(),
+                _ => continue,
+            };
+            let value = graphics::Text::new(value);
+            canvas.draw(&value, graphics::DrawParam::new().dest(Vec2::new(400.0, y)).color(Color::WHITE));
+        }
+    }
+
+    fn mouse_down(&mut self, ctx: &mut Context, shared: &mut Shared, _button: MouseButton, _x: f32, y: f32) -> Transition {
+        if y < LIST_TOP {
+            return Transition::Stay;
+        }
+        let index = ((y - LIST_TOP) / ROW_HEIGHT) as usize;
+        if index >= ENTRIES.len() {
+            return Transition::Stay;
+        }
+        self.selected = index;
+        self.activate(ctx, shared)
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, keycode: KeyCode) -> Transition {
+        match keycode {
+            KeyCode::Up => self.selected = (self.selected + ENTRIES.len() - 1) % ENTRIES.len(),
+            KeyCode::Down => self.selected = (self.selected + 1) % ENTRIES.len(),
+            KeyCode::Return => return self.activate(ctx, shared),
+            KeyCode::Back => {
+                if let Some(input) = self.input() {
+                    input.pop();
+                }
+            }
+            _ => (),
+        }
+        Transition::Stay
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) -> Transition {
+        if let Some(input) = self.input() {
+            input.push(character);
+        }
+        Transition::Stay
+    }
+}
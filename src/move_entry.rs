@@ -0,0 +1,114 @@
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::Pos;
+use chess_gui::notation;
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::Context;
+use glam::Vec2;
+
+use crate::utils::*;
+
+const HEIGHT: f32 = 72.0;
+const MARGIN: f32 = 12.0;
+// More candidates than this don't fit on the line
+const SHOWN_CANDIDATES: usize = 12;
+
+// A box over the bottom of the board to type moves into, in SAN or UCI notation
+pub struct MoveEntry {
+    pub input: String,
+    // Why the last move typed wasn't made
+    pub error: Option<String>,
+    // The legal moves that start with the input
+    candidates: Vec<String>,
+}
+
+impl MoveEntry {
+    pub fn new() -> MoveEntry {
+        MoveEntry {
+            input: String::new(),
+            error: None,
+            candidates: Vec::new(),
+        }
+    }
+
+    // Has to be called whenever the input or the position changes
+    pub fn refresh(&mut self, board: &Board) {
+        self.candidates = if self.input.is_empty() { Vec::new() } else { notation::complete(board, &self.input) };
+    }
+
+    pub fn push(&mut self, character: char, board: &Board) {
+        self.input.push(character);
+        self.error = None;
+        self.refresh(board);
+    }
+
+    pub fn pop(&mut self, board: &Board) {
+        self.input.pop();
+        self.error = None;
+        self.refresh(board);
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.error = None;
+        self.candidates.clear();
+    }
+
+    // Completes as much as all candidates have in common, or the first candidate when that adds
+    // nothing
+    pub fn complete(&mut self, board: &Board) {
+        let first = match self.candidates.first() {
+            Some(first) => first.clone(),
+            None => return,
+        };
+        if self.candidates.contains(&self.input) {
+            return;
+        }
+        let common = self.candidates.iter().fold(first.len(), |len, c| {
+            first.chars().zip(c.chars()).take(len).take_while(|(a, b)| a == b).count()
+        });
+        self.input = if common > self.input.len() { first[..common].to_string() } else { first };
+        self.refresh(board);
+    }
+
+    // The move that was typed. The input is kept when it can't be read so it can be corrected.
+    pub fn submit(&mut self, board: &Board) -> Option<(Pos, Pos, Option<PieceType>)> {
+        match notation::parse_move(board, &self.input) {
+            Ok(m) => {
+                self.clear();
+                Some(m)
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    pub fn draw(&self, ctx: &mut Context, canvas: &mut Canvas) {
+        let top = SCREEN_DIMENSIONS.1 as f32 - HEIGHT - MARGIN;
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: MARGIN, y: top, w: SCREEN_DIMENSIONS.0 as f32 - 2.0 * MARGIN, h: HEIGHT },
+            Color::new(0.1, 0.1, 0.1, 0.85)).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        let text = graphics::Text::new(format!("Move: {}_", self.input));
+        canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(2.0 * MARGIN, top + 10.0)).color(Color::WHITE));
+        let (line, color) = match &self.error {
+            Some(e) => (e.clone(), Color::from_rgb(255, 120, 120)),
+            None if self.input.is_empty() => ("Type a move, e.g. Nf3 or g1f3. Tab completes it.".to_string(), Color::from_rgb(160, 160, 160)),
+            None if self.candidates.is_empty() => ("No legal move starts like this".to_string(), Color::from_rgb(160, 160, 160)),
+            None => {
+                let mut shown = self.candidates.iter().take(SHOWN_CANDIDATES).cloned().collect::<Vec<_>>().join("  ");
+                if self.candidates.len() > SHOWN_CANDIDATES {
+                    shown.push_str("  ...");
+                }
+                (shown, Color::from_rgb(180, 180, 120))
+            }
+        };
+        let text = graphics::Text::new(line);
+        canvas.draw(&text, graphics::DrawParam::new().dest(Vec2::new(2.0 * MARGIN, top + 40.0)).color(color));
+    }
+}
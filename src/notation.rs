@@ -0,0 +1,321 @@
+use crate::networking::Piece;
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+
+// Squares are sent over the network as indices 0-63, counted the same way as the board is stored:
+// x is the file and y the row, starting from the top of the board.
+pub fn pos_to_square(pos: Pos) -> u32 {
+    (pos.x + pos.y * 8) as u32
+}
+
+pub fn square_to_pos(square: u32) -> Pos {
+    Pos {
+        x: (square % 8) as i8,
+        y: (square / 8) as i8,
+    }
+}
+
+// The square in algebraic notation, e.g. "e4"
+pub fn pos_name(pos: Pos) -> String {
+    format!("{}{}", (b'a' + pos.x as u8) as char, 8 - pos.y)
+}
+
+fn piece_char(t: PieceType, c: Color) -> char {
+    let ch = match t {
+        PieceType::Pawn => 'p',
+        PieceType::Knight => 'n',
+        PieceType::Bishop => 'b',
+        PieceType::Rook => 'r',
+        PieceType::Queen => 'q',
+        PieceType::King => 'k',
+    };
+    if c == Color::White { ch.to_ascii_uppercase() } else { ch }
+}
+
+// The standard starting position, with the castling rights `board_to_fen` can't know about
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Builds a FEN string of the current position. The board doesn't keep track of castling rights,
+// en passant squares or move counters so those fields are filled in with neutral values.
+pub fn board_to_fen(board: &Board) -> String {
+    let mut fen = String::new();
+    for y in 0..8 {
+        let mut empty = 0;
+        for x in 0..8 {
+            match &board.board[y][x] {
+                Some(p) => {
+                    if empty > 0 {
+                        fen.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen.push(piece_char(p.get_type(), p.get_color()));
+                }
+                None => empty += 1,
+            }
+        }
+        if empty > 0 {
+            fen.push_str(&empty.to_string());
+        }
+        if y != 7 {
+            fen.push('/');
+        }
+    }
+    fen.push_str(if board.turn == Color::White { " w" } else { " b" });
+    fen.push_str(" - - 0 1");
+    fen
+}
+
+pub fn board_from_fen(fen: &str) -> Option<Board> {
+    Board::from_fen(fen).ok()
+}
+
+// Converts the promotion field of a network move to the piece type the board expects
+pub fn promotion_from_proto(promotion: Option<i32>) -> Option<PieceType> {
+    match Piece::from_i32(promotion?)? {
+        Piece::Knight => Some(PieceType::Knight),
+        Piece::Bishop => Some(PieceType::Bishop),
+        Piece::Rook => Some(PieceType::Rook),
+        Piece::Queen => Some(PieceType::Queen),
+        Piece::Pawn | Piece::King => None,
+    }
+}
+
+pub fn promotion_to_proto(promotion: Option<PieceType>) -> Option<i32> {
+    let piece = match promotion? {
+        PieceType::Knight => Piece::Knight,
+        PieceType::Bishop => Piece::Bishop,
+        PieceType::Rook => Piece::Rook,
+        PieceType::Queen => Piece::Queen,
+        PieceType::Pawn | PieceType::King => return None,
+    };
+    Some(piece as i32)
+}
+
+fn piece_letter(t: PieceType) -> Option<char> {
+    match t {
+        PieceType::Pawn => None,
+        t => Some(piece_char(t, Color::White)),
+    }
+}
+
+fn piece_from_letter(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(PieceType::Knight),
+        'B' => Some(PieceType::Bishop),
+        'R' => Some(PieceType::Rook),
+        'Q' => Some(PieceType::Queen),
+        'K' => Some(PieceType::King),
+        _ => None,
+    }
+}
+
+// Parses a square in algebraic notation, e.g. "e4"
+pub fn parse_square(name: &str) -> Option<Pos> {
+    let mut chars = name.chars();
+    let file = chars.next().filter(|c| ('a'..='h').contains(c))?;
+    let rank = chars.next().and_then(|c| c.to_digit(10)).filter(|r| (1..=8).contains(r))?;
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(Pos {
+        x: (file as u8 - b'a') as i8,
+        y: 8 - rank as i8,
+    })
+}
+
+// A move in UCI notation, e.g. "e2e4" or "e7e8q"
+pub fn move_to_uci(from: Pos, to: Pos, promotion: Option<PieceType>) -> String {
+    let promotion = promotion.and_then(piece_letter).map_or(String::new(), |c| c.to_ascii_lowercase().to_string());
+    format!("{}{}{}", pos_name(from), pos_name(to), promotion)
+}
+
+pub fn uci_to_move(uci: &str) -> Option<(Pos, Pos, Option<PieceType>)> {
+    let from = parse_square(uci.get(0..2)?)?;
+    let to = parse_square(uci.get(2..4)?)?;
+    let promotion = match uci.get(4..) {
+        Some("") | None => None,
+        Some(p) if p.len() == 1 => Some(piece_from_letter(p.chars().next()?.to_ascii_uppercase())?),
+        Some(_) => return None,
+    };
+    Some((from, to, promotion))
+}
+
+// Describes a move of the side to move in standard algebraic notation, e.g. "Nbd2", "exd5" or
+// "e8=Q". Check and mate aren't marked since the board can't try a move without making it.
+pub fn move_to_san(board: &Board, from: Pos, to: Pos, promotion: Option<PieceType>) -> String {
+    let piece = match &board.board[from.y as usize][from.x as usize] {
+        Some(p) => p.get_type(),
+        None => return move_to_uci(from, to, promotion),
+    };
+    let capture = board.board[to.y as usize][to.x as usize].is_some();
+    if piece == PieceType::King && (to.x - from.x).abs() == 2 {
+        return if to.x > from.x { "O-O".to_string() } else { "O-O-O".to_string() };
+    }
+    let mut san = String::new();
+    match piece_letter(piece) {
+        Some(letter) => {
+            san.push(letter);
+            // Other pieces of the same kind that could go to the same square
+            let others: Vec<Pos> = crate::rules::legal_moves(board)
+                .into_iter()
+                .filter(|&(f, t)| t == to && f != from)
+                .filter(|&(f, _)| board.board[f.y as usize][f.x as usize].as_ref().map(|p| p.get_type()) == Some(piece))
+                .map(|(f, _)| f)
+                .collect();
+            let name = pos_name(from);
+            if !others.is_empty() {
+                if others.iter().all(|o| o.x != from.x) {
+                    san.push_str(&name[0..1]);
+                } else if others.iter().all(|o| o.y != from.y) {
+                    san.push_str(&name[1..2]);
+                } else {
+                    san.push_str(&name);
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+        }
+        None => {
+            // En passant captures land on an empty square but still change file
+            if capture || from.x != to.x {
+                san.push_str(&pos_name(from)[0..1]);
+                san.push('x');
+            }
+        }
+    }
+    san.push_str(&pos_name(to));
+    if let Some(letter) = promotion.and_then(piece_letter) {
+        san.push('=');
+        san.push(letter);
+    }
+    san
+}
+
+// Finds the move of the side to move that `san` describes. Check and mate markers and
+// annotations like "!?" are ignored, "0-0" is accepted for castling.
+pub fn san_to_move(board: &Board, san: &str) -> Result<(Pos, Pos, Option<PieceType>), String> {
+    let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+    let no_move = || format!("{} isn't a legal move", san.trim());
+    let moves = crate::rules::legal_moves(board);
+    let piece_at = |p: Pos| board.board[p.y as usize][p.x as usize].as_ref().map(|p| p.get_type());
+
+    if matches!(text, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+        let long = text.len() == 5;
+        return moves
+            .into_iter()
+            .find(|&(f, t)| piece_at(f) == Some(PieceType::King) && t.x - f.x == if long { -2 } else { 2 })
+            .map(|(f, t)| (f, t, None))
+            .ok_or_else(no_move);
+    }
+
+    // "e8=Q" and "e8Q" both promote
+    let (text, promotion) = match text.char_indices().last() {
+        Some((i, c)) if piece_from_letter(c).is_some() && i >= 2 => {
+            (text[..i].trim_end_matches('='), piece_from_letter(c))
+        }
+        _ => (text, None),
+    };
+    if text.len() < 2 || !text.is_char_boundary(text.len() - 2) {
+        return Err(no_move());
+    }
+    let to = parse_square(&text[text.len() - 2..]).ok_or_else(no_move)?;
+    let mut rest = &text[..text.len() - 2];
+    let piece = match rest.chars().next().and_then(piece_from_letter) {
+        Some(p) => {
+            rest = &rest[1..];
+            p
+        }
+        None => PieceType::Pawn,
+    };
+    // What is left says where the piece comes from, e.g. the "b" in "Nbd2" or "exd5"
+    let rest = rest.trim_end_matches(['x', ':']);
+    let mut file = None;
+    let mut rank = None;
+    for c in rest.chars() {
+        match c {
+            'a'..='h' => file = Some((c as u8 - b'a') as i8),
+            '1'..='8' => rank = Some(8 - c.to_digit(10).unwrap_or(0) as i8),
+            _ => return Err(no_move()),
+        }
+    }
+
+    let candidates: Vec<(Pos, Pos)> = moves
+        .into_iter()
+        .filter(|&(f, t)| t == to && piece_at(f) == Some(piece))
+        .filter(|&(f, _)| file.is_none_or(|x| f.x == x) && rank.is_none_or(|y| f.y == y))
+        .collect();
+    match candidates.as_slice() {
+        [] => Err(no_move()),
+        [(from, to)] => check_promotion(board, san.trim(), *from, *to, promotion),
+        several => Err(format!(
+            "{} is ambiguous, it could be {}",
+            san.trim(),
+            several.iter().map(|&(f, t)| move_to_san(board, f, t, promotion)).collect::<Vec<_>>().join(" or ")
+        )),
+    }
+}
+
+// Wether the move takes a pawn to the last rank
+fn promotes(board: &Board, from: Pos, to: Pos) -> bool {
+    let pawn = board.board[from.y as usize][from.x as usize].as_ref().map(|p| p.get_type()) == Some(PieceType::Pawn);
+    pawn && (to.y == 0 || to.y == 7)
+}
+
+const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+// A pawn reaching the last rank has to say what it becomes, no other move may
+fn check_promotion(board: &Board, text: &str, from: Pos, to: Pos, promotion: Option<PieceType>) -> Result<(Pos, Pos, Option<PieceType>), String> {
+    match (promotes(board, from, to), promotion) {
+        (true, None) => Err(format!("{} has to say what the pawn promotes to, e.g. {}=Q", text, pos_name(to))),
+        (false, Some(_)) => Err(format!("{} isn't a legal move", text)),
+        _ => Ok((from, to, promotion)),
+    }
+}
+
+// Reads a move typed by the player, in UCI notation like "g1f3" or otherwise in SAN
+pub fn parse_move(board: &Board, text: &str) -> Result<(Pos, Pos, Option<PieceType>), String> {
+    let text = text.trim();
+    match uci_to_move(text) {
+        Some((from, to, promotion)) if crate::rules::legal_moves(board).contains(&(from, to)) => {
+            check_promotion(board, text, from, to, promotion)
+        }
+        Some(_) => Err(format!("{} isn't a legal move", text)),
+        None => san_to_move(board, text),
+    }
+}
+
+// Every legal move of the side to move in SAN, a promotion once for every piece it can become
+pub fn legal_sans(board: &Board) -> Vec<String> {
+    let mut sans = Vec::new();
+    for (from, to) in crate::rules::legal_moves(board) {
+        if promotes(board, from, to) {
+            sans.extend(PROMOTIONS.iter().map(|&p| move_to_san(board, from, to, Some(p))));
+        } else {
+            sans.push(move_to_san(board, from, to, None));
+        }
+    }
+    sans.sort();
+    sans
+}
+
+// The legal moves that start with what has been typed so far. SAN is matched first, UCI when no
+// SAN move fits, e.g. after "g1".
+pub fn complete(board: &Board, prefix: &str) -> Vec<String> {
+    let prefix = prefix.trim();
+    let sans: Vec<String> = legal_sans(board).into_iter().filter(|m| m.starts_with(prefix)).collect();
+    if !sans.is_empty() {
+        return sans;
+    }
+    let mut ucis: Vec<String> = crate::rules::legal_moves(board)
+        .into_iter()
+        .flat_map(|(from, to)| match promotes(board, from, to) {
+            true => PROMOTIONS.iter().map(|&p| move_to_uci(from, to, Some(p))).collect(),
+            false => vec![move_to_uci(from, to, None)],
+        })
+        .filter(|m| m.starts_with(prefix))
+        .collect();
+    ucis.sort();
+    ucis
+}
@@ -0,0 +1,46 @@
+// Names of openings by their moves, e.g. "C60 Ruy Lopez" after 1. e4 e5 2. Nf3 Nc6 3. Bb5. The
+// list is built in, with the ECO code, the name and the moves in SAN on every line.
+const ECO: &str = include_str!("resources/eco.tsv");
+
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+    pub moves: Vec<String>,
+}
+
+// Check and mate markers are left out when comparing, the moves of a game may not have them
+fn strip(san: &str) -> &str {
+    san.trim_end_matches(['+', '#'])
+}
+
+pub struct Openings {
+    openings: Vec<Opening>,
+}
+
+impl Openings {
+    pub fn embedded() -> Openings {
+        Openings::parse(ECO)
+    }
+
+    // Lines that aren't "eco, name, moves" separated by tabs are skipped, like the header
+    pub fn parse(text: &str) -> Openings {
+        let openings = text
+            .lines()
+            .filter_map(|line| {
+                let mut columns = line.split('\t');
+                let (eco, name, pgn) = (columns.next()?, columns.next()?, columns.next()?);
+                let moves: Vec<String> = pgn.split_whitespace().filter(|t| !t.ends_with('.')).map(|t| strip(t).to_string()).collect();
+                (!moves.is_empty() && eco != "eco").then(|| Opening { eco: eco.to_string(), name: name.to_string(), moves })
+            })
+            .collect();
+        Openings { openings }
+    }
+
+    // The opening with the most moves that the game started with
+    pub fn find(&self, moves: &[String]) -> Option<&Opening> {
+        self.openings
+            .iter()
+            .filter(|o| o.moves.len() <= moves.len() && o.moves.iter().zip(moves).all(|(a, b)| a == strip(b)))
+            .max_by_key(|o| o.moves.len())
+    }
+}
@@ -0,0 +1,132 @@
+use crate::notation::*;
+use chess::board::Board;
+use std::path::Path;
+
+// A game read from a PGN file. Only the first game of a file is read.
+#[derive(Debug, Default, PartialEq)]
+pub struct Pgn {
+    pub tags: Vec<(String, String)>,
+    // In standard algebraic notation, without move numbers
+    pub moves: Vec<String>,
+    pub result: Option<String>,
+}
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+impl Pgn {
+    pub fn load(path: &Path) -> Result<Pgn, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Pgn::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Pgn, String> {
+        let mut pgn = Pgn::default();
+        let mut lines = text.lines().map(str::trim).peekable();
+        while let Some(line) = lines.next_if(|l| l.is_empty() || l.starts_with('[')) {
+            if let Some(tag) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let (name, value) = tag.split_once(' ').ok_or_else(|| format!("Malformed tag: {}", line))?;
+                pgn.tags.push((name.to_string(), value.trim().trim_matches('"').to_string()));
+            }
+        }
+
+        // Comments and variations are skipped, a blank line ends the game
+        let mut depth = 0;
+        let mut in_comment = false;
+        let mut movetext = String::new();
+        for line in lines.take_while(|l| !l.is_empty()) {
+            // The rest of a line after a semicolon is a comment
+            let line = if in_comment { line } else { line.split(';').next().unwrap_or("") };
+            for c in line.chars() {
+                match c {
+                    '{' => in_comment = true,
+                    '}' => in_comment = false,
+                    '(' if !in_comment => depth += 1,
+                    ')' if !in_comment => depth -= 1,
+                    c if !in_comment && depth == 0 => movetext.push(c),
+                    _ => (),
+                }
+            }
+            movetext.push(' ');
+        }
+
+        for token in movetext.split_whitespace() {
+            if RESULTS.contains(&token) {
+                pgn.result = Some(token.to_string());
+                break;
+            }
+            // Move numbers are written as "12." or "12..." and may be stuck to the move
+            let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+            // Numeric annotation glyphs like "$1"
+            if token.is_empty() || token.starts_with('$') {
+                continue;
+            }
+            pgn.moves.push(token.to_string());
+        }
+        Ok(pgn)
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    // The position before the first move, from the FEN tag if there is one
+    pub fn start(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => board_from_fen(fen).ok_or_else(|| format!("Invalid FEN: {}", fen)),
+            None => Ok(Board::new()),
+        }
+    }
+
+    // The position before the first move and after every move as FEN
+    pub fn positions(&self) -> Result<Vec<String>, String> {
+        let mut board = self.start()?;
+        let mut positions = vec![board_to_fen(&board)];
+        for (i, san) in self.moves.iter().enumerate() {
+            let (from, to, promotion) = san_to_move(&board, san).map_err(|e| format!("Move {}: {}", i / 2 + 1, e))?;
+            board
+                .perform_move(from, to, promotion)
+                .map_err(|_| format!("Move {}: {} isn't a legal move", i / 2 + 1, san))?;
+            positions.push(board_to_fen(&board));
+        }
+        Ok(positions)
+    }
+
+    // The same positions as boards, e.g. to draw them
+    pub fn boards(&self) -> Result<Vec<Board>, String> {
+        self.positions()?
+            .iter()
+            .map(|fen| board_from_fen(fen).ok_or_else(|| format!("Invalid position: {}", fen)))
+            .collect()
+    }
+}
+
+// Writes the game back out as PGN, ten moves to a line
+impl std::fmt::Display for Pgn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (name, value) in &self.tags {
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+        // A game from a position with black to move starts with "1..."
+        let black_first = self.tag("FEN").is_some_and(|fen| fen.split_whitespace().nth(1) == Some("b"));
+        let offset = usize::from(black_first);
+        for (i, san) in self.moves.iter().enumerate() {
+            let ply = i + offset;
+            if i > 0 {
+                write!(f, "{}", if ply % 20 == 0 { "\n" } else { " " })?;
+            }
+            if ply % 2 == 0 {
+                write!(f, "{}. ", ply / 2 + 1)?;
+            } else if i == 0 {
+                write!(f, "{}... ", ply / 2 + 1)?;
+            }
+            write!(f, "{}", san)?;
+        }
+        let result = self.result.as_deref().unwrap_or("*");
+        if self.moves.is_empty() {
+            writeln!(f, "{}", result)
+        } else {
+            writeln!(f, " {}", result)
+        }
+    }
+}
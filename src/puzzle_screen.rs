@@ -0,0 +1,275 @@
+use chess::piece::PieceType;
+use chess::util::Pos;
+use chess_gui::accessibility;
+use chess_gui::puzzles::{Attempt, History, Puzzle, Verdict};
+use chess_gui::render::BoardView;
+use chess_gui::sound::Sound;
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::board_view::{draw_view, view};
+use crate::move_entry::MoveEntry;
+use crate::scene::{Scene, Shared, Transition};
+use crate::utils::*;
+
+const LINE_HEIGHT: f32 = 28.0;
+const MARGIN: f32 = 12.0;
+// How long the opponent waits before answering, so the player sees their move first
+const REPLY_DELAY: Duration = Duration::from_millis(500);
+
+// Plays through puzzles one after the other, the closest to the player's rating first. Moves
+// are made with the mouse or typed in.
+pub struct PuzzleScreen {
+    puzzles: Vec<Puzzle>,
+    history: History,
+    history_path: PathBuf,
+    attempt: Attempt,
+    selected_pos: Option<Pos>,
+    highlights: Vec<Pos>,
+    // When the opponent makes its next move
+    reply_at: Option<Instant>,
+    // What became of the last move and what to say about it
+    feedback: Option<(Verdict, String)>,
+    move_entry: MoveEntry,
+}
+
+impl PuzzleScreen {
+    // Fails if every puzzle has been tried or the next one is broken
+    pub fn new(puzzles: Vec<Puzzle>, history: History, history_path: PathBuf) -> Result<PuzzleScreen, String> {
+        let attempt = PuzzleScreen::next_attempt(&puzzles, &history)?;
+        let mut move_entry = MoveEntry::new();
+        move_entry.refresh(&attempt.board);
+        Ok(PuzzleScreen {
+            puzzles,
+            history,
+            history_path,
+            attempt,
+            selected_pos: None,
+            highlights: Vec::new(),
+            reply_at: None,
+            feedback: None,
+            move_entry,
+        })
+    }
+
+    fn next_attempt(puzzles: &[Puzzle], history: &History) -> Result<Attempt, String> {
+        let puzzle = history.next(puzzles).ok_or_else(|| "You have tried every puzzle in the file".to_string())?;
+        Attempt::start(puzzle.clone())
+    }
+
+    // Moves on to the next puzzle once this one is over
+    fn next_puzzle(&mut self, shared: &mut Shared) {
+        match PuzzleScreen::next_attempt(&self.puzzles, &self.history) {
+            Ok(attempt) => {
+                self.attempt = attempt;
+                self.selected_pos = None;
+                self.highlights = Vec::new();
+                self.feedback = None;
+                self.move_entry.clear();
+                self.move_entry.refresh(&self.attempt.board);
+                shared.message = None;
+                self.announce_start(shared);
+            }
+            Err(e) => shared.message = Some(e),
+        }
+    }
+
+    // Says what the opponent did and what the player is looking for
+    pub fn announce_start(&self, shared: &Shared) {
+        if let Some(played) = &self.attempt.last_move {
+            shared.announce(&accessibility::describe_move(played, &self.attempt.board));
+        }
+        shared.announce(&format!("Find the best move for {}", color_name(self.attempt.color)));
+    }
+
+    fn is_flipped(&self, shared: &Shared) -> bool {
+        shared.settings.orientation.is_flipped(Some(self.attempt.color))
+    }
+
+    // Wether the player may move now, not while the opponent is about to answer
+    fn can_move(&self) -> bool {
+        self.attempt.is_players_turn() && self.reply_at.is_none()
+    }
+
+    // Checks the move against the solution and rates the player once the puzzle is over
+    fn play(&mut self, ctx: &Context, shared: &mut Shared, from: Pos, to: Pos, promotion: Option<PieceType>) {
+        self.selected_pos = None;
+        self.highlights = Vec::new();
+        // Asked before the move since a right one is made on the board
+        let solution = self.attempt.solution().unwrap_or_default();
+        let verdict = self.attempt.try_move(from, to, promotion);
+        if let (Verdict::Correct | Verdict::Solved, Some(played)) = (verdict, &self.attempt.last_move) {
+            shared.announce(&accessibility::describe_move(played, &self.attempt.board));
+            shared.play(ctx, Sound::for_move(played, &self.attempt.board));
+        }
+        let text = match verdict {
+            Verdict::Correct => {
+                self.reply_at = Some(Instant::now() + REPLY_DELAY);
+                "Correct, keep going".to_string()
+            }
+            Verdict::Solved | Verdict::Wrong => {
+                let solved = verdict == Verdict::Solved;
+                let before = self.history.rating();
+                let rating = match self.history.record(&self.history_path, &self.attempt.puzzle, solved).map(|e| e.rating) {
+                    Ok(rating) => rating,
+                    Err(e) => {
+                        shared.message = Some(e);
+                        self.history.rating()
+                    }
+                };
+                let change = format!("your rating is {} ({:+})", rating, rating - before);
+                if solved {
+                    shared.play(ctx, Sound::GameEnd);
+                    format!("Solved, {}", change)
+                } else {
+                    format!("Wrong, the answer was {}, {}", solution, change)
+                }
+            }
+        };
+        shared.announce(&text);
+        self.feedback = Some((verdict, text));
+        self.move_entry.refresh(&self.attempt.board);
+    }
+
+    // Makes the opponent's answer once it is due
+    fn update_reply(&mut self, ctx: &Context, shared: &mut Shared) {
+        match self.reply_at {
+            Some(at) if Instant::now() >= at => self.reply_at = None,
+            _ => return,
+        }
+        if let Some(played) = self.attempt.opponent_move().cloned() {
+            shared.announce(&accessibility::describe_move(&played, &self.attempt.board));
+            shared.play(ctx, Sound::for_move(&played, &self.attempt.board));
+        }
+        self.move_entry.refresh(&self.attempt.board);
+    }
+
+    fn submit_move(&mut self, ctx: &Context, shared: &mut Shared) {
+        if !self.can_move() {
+            self.move_entry.error = Some("Wait for your opponent's move".to_string());
+        } else if let Some((from, to, promotion)) = self.move_entry.submit(&self.attempt.board) {
+            self.play(ctx, shared, from, to, promotion);
+        }
+        if let Some(e) = &self.move_entry.error {
+            shared.announce(e);
+        }
+    }
+
+    // The puzzle, the player's rating and how the last move went
+    fn draw_panel(&self, ctx: &mut Context, canvas: &mut Canvas) {
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: SCREEN_DIMENSIONS.0 as f32, y: 0.0, w: PANEL_WIDTH as f32, h: SCREEN_DIMENSIONS.1 as f32 },
+            Color::from_rgb(30, 30, 30)).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        let puzzle = &self.attempt.puzzle;
+        let turn = if self.attempt.is_over() {
+            String::new()
+        } else {
+            format!("Find the best move for {}", color_name(self.attempt.color))
+        };
+        let lines = [
+            (format!("Puzzle {}, rated {}", puzzle.id, puzzle.rating), Color::WHITE),
+            (turn, Color::WHITE),
+            (format!("Your rating: {}", self.history.rating()), Color::WHITE),
+            (format!("Solved {} of {} tried", self.history.solved(), self.history.entries.len()), Color::WHITE),
+            (String::new(), Color::WHITE),
+            match &self.feedback {
+                Some((Verdict::Wrong, text)) => (text.clone(), Color::from_rgb(255, 120, 120)),
+                Some((_, text)) => (text.clone(), Color::from_rgb(120, 220, 120)),
+                None => (String::new(), Color::WHITE),
+            },
+            (String::new(), Color::WHITE),
+            // The themes give the puzzle away, they are shown afterwards
+            (if self.attempt.is_over() { puzzle.themes.join(", ") } else { String::new() }, Color::from_rgb(180, 180, 180)),
+            (String::new(), Color::WHITE),
+            ("Type a move or click the pieces".to_string(), Color::WHITE),
+            ("Return: next puzzle once this one is over".to_string(), Color::WHITE),
+            ("F5: describe the position, Escape: back".to_string(), Color::WHITE),
+        ];
+        let left = SCREEN_DIMENSIONS.0 as f32 + MARGIN;
+        for (i, (line, color)) in lines.into_iter().enumerate() {
+            let text = graphics::Text::new(line);
+            let dst = Vec2::new(left, MARGIN + i as f32 * LINE_HEIGHT);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(color));
+        }
+    }
+}
+
+fn color_name(color: chess::util::Color) -> &'static str {
+    if color == chess::util::Color::White { "White" } else { "Black" }
+}
+
+impl Scene for PuzzleScreen {
+    fn update(&mut self, ctx: &mut Context, shared: &mut Shared) -> Transition {
+        self.update_reply(ctx, shared);
+        Transition::Stay
+    }
+
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared) {
+        // The squares of the last move are marked so the opponent's answer can be followed
+        let marked: Vec<Pos> = self.attempt.last_move.iter().flat_map(|m| [m.from, m.to]).collect();
+        let shown = BoardView {
+            selected: self.selected_pos,
+            highlights: &self.highlights,
+            marked: &marked,
+            ..view(shared, &self.attempt.board, self.is_flipped(shared))
+        };
+        draw_view(ctx, canvas, shared, &shown);
+        if !self.move_entry.input.is_empty() || self.move_entry.error.is_some() {
+            self.move_entry.draw(ctx, canvas);
+        }
+        self.draw_panel(ctx, canvas);
+    }
+
+    fn mouse_down(&mut self, ctx: &mut Context, shared: &mut Shared, button: MouseButton, x: f32, y: f32) -> Transition {
+        if button != MouseButton::Left || !self.can_move() {
+            return Transition::Stay;
+        }
+        let pos = match square_at(x, y) {
+            Some(pos) => flip(pos, self.is_flipped(shared)),
+            None => return Transition::Stay,
+        };
+        if let Some(p) = self.selected_pos {
+            if self.highlights.contains(&pos) {
+                self.play(ctx, shared, p, pos, None);
+                return Transition::Stay;
+            }
+        }
+        let board = &self.attempt.board;
+        let ours = board.board[pos.y as usize][pos.x as usize].as_ref().is_some_and(|p| p.get_color() == self.attempt.color);
+        self.selected_pos = ours.then_some(pos);
+        self.highlights = if ours { board.get_possible_moves_at_square(pos) } else { Vec::new() };
+        Transition::Stay
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, keycode: KeyCode) -> Transition {
+        match keycode {
+            // A half typed move is thrown away first
+            KeyCode::Escape if !self.move_entry.input.is_empty() => self.move_entry.clear(),
+            KeyCode::Escape => return Transition::Pop,
+            KeyCode::F5 => shared.announce(&accessibility::describe_position(&self.attempt.board)),
+            KeyCode::Back => self.move_entry.pop(&self.attempt.board),
+            KeyCode::Tab => self.move_entry.complete(&self.attempt.board),
+            KeyCode::Return if !self.move_entry.input.is_empty() => self.submit_move(ctx, shared),
+            KeyCode::Return if self.attempt.is_over() => self.next_puzzle(shared),
+            _ => (),
+        }
+        Transition::Stay
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) -> Transition {
+        // Spaces would only get in the way of reading the move
+        if !character.is_whitespace() {
+            self.move_entry.push(character, &self.attempt.board);
+        }
+        Transition::Stay
+    }
+}
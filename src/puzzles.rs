@@ -0,0 +1,302 @@
+use crate::notation::{board_from_fen, move_to_san, uci_to_move};
+use crate::rules::{self, Outcome};
+use crate::session::PlayedMove;
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Players start with the rating the Lichess puzzles are centered on
+pub const START_RATING: i32 = 1500;
+// How far a single puzzle can move the rating
+const K_FACTOR: f64 = 32.0;
+
+// Where the rating and the puzzles tried so far are kept
+pub fn default_history_path() -> PathBuf {
+    crate::tls::default_dir().join("puzzles.csv")
+}
+
+// A puzzle from the Lichess puzzle database
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    pub id: String,
+    // The position before the opponent's move that sets up the puzzle
+    pub fen: String,
+    // In UCI notation, the opponent's and the player's moves taking turns starting with the
+    // opponent's
+    pub moves: Vec<String>,
+    pub rating: i32,
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    // A line of the database: PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,
+    // Themes,GameUrl,OpeningTags. Only the first four columns are needed.
+    pub fn parse(line: &str) -> Result<Puzzle, String> {
+        let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+        if columns.len() < 4 {
+            return Err(format!("Not a puzzle: {}", line));
+        }
+        let moves: Vec<String> = columns[2].split_whitespace().map(str::to_string).collect();
+        if moves.len() < 2 || moves.iter().any(|m| uci_to_move(m).is_none()) {
+            return Err(format!("Invalid moves in puzzle {}: {}", columns[0], columns[2]));
+        }
+        let rating = columns[3].parse().map_err(|_| format!("Invalid rating in puzzle {}: {}", columns[0], columns[3]))?;
+        Ok(Puzzle {
+            id: columns[0].to_string(),
+            fen: columns[1].to_string(),
+            moves,
+            rating,
+            themes: columns.get(7).map_or(Vec::new(), |t| t.split_whitespace().map(str::to_string).collect()),
+        })
+    }
+}
+
+// Every puzzle in `text`, the header and empty lines are skipped
+pub fn parse_puzzles(text: &str) -> Result<Vec<Puzzle>, String> {
+    let mut puzzles = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("PuzzleId") {
+            continue;
+        }
+        puzzles.push(Puzzle::parse(line).map_err(|e| format!("Line {}: {}", i + 1, e))?);
+    }
+    Ok(puzzles)
+}
+
+// The whole database is large, a part of it cut out with e.g. `head` loads a lot faster
+pub fn load_puzzles(path: &Path) -> Result<Vec<Puzzle>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let puzzles = parse_puzzles(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if puzzles.is_empty() {
+        return Err(format!("There are no puzzles in {}", path.display()));
+    }
+    Ok(puzzles)
+}
+
+// What became of a move the player made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verdict {
+    // The move was right and the opponent answers it next
+    Correct,
+    // The move was right and the last one of the solution
+    Solved,
+    Wrong,
+}
+
+// The player working through one puzzle
+pub struct Attempt {
+    pub puzzle: Puzzle,
+    pub board: Board,
+    // The color the player finds the moves for
+    pub color: Color,
+    // The last move made, by either side
+    pub last_move: Option<PlayedMove>,
+    // How many moves of the solution have been made
+    played: usize,
+    failed: bool,
+}
+
+impl Attempt {
+    // Sets up the position and makes the opponent's move that starts the puzzle
+    pub fn start(puzzle: Puzzle) -> Result<Attempt, String> {
+        let board = board_from_fen(&puzzle.fen).ok_or_else(|| format!("Invalid position in puzzle {}: {}", puzzle.id, puzzle.fen))?;
+        let color = if board.turn == Color::White { Color::Black } else { Color::White };
+        let mut attempt = Attempt { puzzle, board, color, last_move: None, played: 0, failed: false };
+        if attempt.opponent_move().is_none() {
+            return Err(format!("The first move of puzzle {} isn't legal", attempt.puzzle.id));
+        }
+        Ok(attempt)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.failed || self.played >= self.puzzle.moves.len()
+    }
+
+    pub fn is_solved(&self) -> bool {
+        !self.failed && self.played >= self.puzzle.moves.len()
+    }
+
+    // The player is to move, the opponent's moves are odd
+    pub fn is_players_turn(&self) -> bool {
+        !self.is_over() && self.played % 2 == 1
+    }
+
+    // The move of the solution that comes next
+    fn expected(&self) -> Option<(Pos, Pos, Option<PieceType>)> {
+        uci_to_move(self.puzzle.moves.get(self.played)?)
+    }
+
+    // The move the player should make now in SAN, e.g. to show after a wrong one
+    pub fn solution(&self) -> Option<String> {
+        let (from, to, promotion) = self.expected()?;
+        Some(move_to_san(&self.board, from, to, promotion))
+    }
+
+    fn perform(&mut self, from: Pos, to: Pos, promotion: Option<PieceType>) -> bool {
+        let san = move_to_san(&self.board, from, to, promotion);
+        if self.board.perform_move(from, to, promotion).is_err() {
+            return false;
+        }
+        self.last_move = Some(PlayedMove { san, from, to, promotion });
+        self.played += 1;
+        true
+    }
+
+    // Makes the opponent's next move of the solution, if it is the opponent's turn
+    pub fn opponent_move(&mut self) -> Option<&PlayedMove> {
+        if self.is_over() || self.played % 2 == 1 {
+            return None;
+        }
+        let (from, to, promotion) = self.expected()?;
+        if !self.perform(from, to, promotion) {
+            return None;
+        }
+        self.last_move.as_ref()
+    }
+
+    // The board as it is now, set up again from the puzzle. A FEN of the board would lose the
+    // castling rights and the en passant square.
+    fn replay(&self) -> Option<Board> {
+        let mut board = board_from_fen(&self.puzzle.fen)?;
+        for m in &self.puzzle.moves[..self.played] {
+            let (from, to, promotion) = uci_to_move(m)?;
+            board.perform_move(from, to, promotion).ok()?;
+        }
+        Some(board)
+    }
+
+    // Whether making the move would checkmate the opponent. It is tried on a copy since the board
+    // can't try a move without making it.
+    fn mates(&self, from: Pos, to: Pos, promotion: Option<PieceType>) -> bool {
+        let mut board = match self.replay() {
+            Some(board) => board,
+            None => return false,
+        };
+        board.perform_move(from, to, promotion).is_ok() && rules::outcome(&board) == Some(Outcome::Checkmate(self.color))
+    }
+
+    // Checks the player's move against the solution. A right move is made on the board, a wrong
+    // one isn't and ends the attempt. Like on Lichess any mate is right for the last move.
+    pub fn try_move(&mut self, from: Pos, to: Pos, promotion: Option<PieceType>) -> Verdict {
+        if !self.is_players_turn() {
+            return Verdict::Wrong;
+        }
+        let (right_from, right_to, right_promotion) = match self.expected() {
+            Some(m) => m,
+            None => return Verdict::Wrong,
+        };
+        // A pawn dragged to the last rank becomes a queen
+        let promotion_matches = promotion == right_promotion || (promotion.is_none() && right_promotion == Some(PieceType::Queen));
+        let last = self.played + 1 == self.puzzle.moves.len();
+        let made = if from == right_from && to == right_to && promotion_matches {
+            self.perform(from, to, right_promotion)
+        } else {
+            last && self.mates(from, to, promotion) && self.perform(from, to, promotion)
+        };
+        if !made {
+            self.failed = true;
+            Verdict::Wrong
+        } else if self.is_over() {
+            Verdict::Solved
+        } else {
+            Verdict::Correct
+        }
+    }
+}
+
+// The player's rating after solving or failing a puzzle rated `puzzle`, the way Elo ratings
+// change after a game
+pub fn new_rating(rating: i32, puzzle: i32, solved: bool) -> i32 {
+    let expected = 1.0 / (1.0 + 10f64.powf((puzzle - rating) as f64 / 400.0));
+    let score = if solved { 1.0 } else { 0.0 };
+    rating + (K_FACTOR * (score - expected)).round() as i32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub solved: bool,
+    // The player's rating after the puzzle
+    pub rating: i32,
+    // Seconds since 1970
+    pub time: u64,
+}
+
+impl HistoryEntry {
+    // A line of the history file: id,solved or failed,rating,time
+    fn parse(line: &str) -> Option<HistoryEntry> {
+        let mut columns = line.split(',').map(str::trim);
+        let id = columns.next()?.to_string();
+        let solved = match columns.next()? {
+            "solved" => true,
+            "failed" => false,
+            _ => return None,
+        };
+        let rating = columns.next()?.parse().ok()?;
+        let time = columns.next().and_then(|t| t.parse().ok()).unwrap_or(0);
+        Some(HistoryEntry { id, solved, rating, time })
+    }
+}
+
+impl std::fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{},{},{},{}", self.id, if self.solved { "solved" } else { "failed" }, self.rating, self.time)
+    }
+}
+
+// Every puzzle the player has tried, oldest first, and the rating after each
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    // An empty history if there is no file yet. Lines that can't be read are skipped so a
+    // damaged file doesn't lose the rest.
+    pub fn load(path: &Path) -> Result<History, String> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(History::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let entries = BufReader::new(file).lines().map_while(Result::ok).filter_map(|l| HistoryEntry::parse(&l)).collect();
+        Ok(History { entries })
+    }
+
+    pub fn rating(&self) -> i32 {
+        self.entries.last().map_or(START_RATING, |e| e.rating)
+    }
+
+    pub fn solved(&self) -> usize {
+        self.entries.iter().filter(|e| e.solved).count()
+    }
+
+    pub fn has_tried(&self, id: &str) -> bool {
+        self.entries.iter().any(|e| e.id == id)
+    }
+
+    // The puzzle not tried yet with the rating closest to the player's
+    pub fn next<'a>(&self, puzzles: &'a [Puzzle]) -> Option<&'a Puzzle> {
+        let rating = self.rating();
+        puzzles.iter().filter(|p| !self.has_tried(&p.id)).min_by_key(|p| (p.rating - rating).abs())
+    }
+
+    // Rates the player for the puzzle and adds it to the end of the file. The entry is kept even
+    // if it can't be saved.
+    pub fn record(&mut self, path: &Path, puzzle: &Puzzle, solved: bool) -> Result<&HistoryEntry, String> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let rating = new_rating(self.rating(), puzzle.rating, solved);
+        self.entries.push(HistoryEntry { id: puzzle.id.clone(), solved, rating, time });
+        let entry = self.entries.last().expect("An entry was just added");
+        let write_error = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path).map_err(write_error)?;
+        writeln!(file, "{}", entry).map_err(write_error)?;
+        Ok(entry)
+    }
+}
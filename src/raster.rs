@@ -0,0 +1,133 @@
+use crate::render::{self, BoardView, Painter, Point, Rect, Rgba};
+use chess::piece::PieceType;
+use chess::util::Color;
+use std::path::Path;
+use tiny_skia::{FillRule, FilterQuality, Paint, PathBuilder, Pixmap, PixmapPaint, Stroke, Transform};
+
+// Draws the board on the CPU into an image in memory, so it can be drawn without a window or a
+// GPU, e.g. to compare it to a known good image in the tests
+pub struct Raster {
+    pixmap: Pixmap,
+    pieces: Vec<((PieceType, Color), Pixmap)>,
+}
+
+fn load(path: &Path) -> Result<Pixmap, String> {
+    Pixmap::load_png(path).map_err(|e| format!("Failed to load {}: {}", path.display(), e))
+}
+
+fn paint(color: Rgba) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(tiny_skia::Color::from_rgba(color.r, color.g, color.b, color.a).unwrap_or(tiny_skia::Color::BLACK));
+    paint.anti_alias = true;
+    paint
+}
+
+fn stroke(width: f32) -> Stroke {
+    Stroke { width, ..Stroke::default() }
+}
+
+impl Raster {
+    // A black image `size` pixels wide and high with the pieces in `piece_dir`, named like the
+    // resources, e.g. w_king.png
+    pub fn new(size: u32, piece_dir: &Path) -> Result<Raster, String> {
+        let mut pixmap = Pixmap::new(size, size).ok_or_else(|| format!("Invalid image size: {}", size))?;
+        pixmap.fill(tiny_skia::Color::BLACK);
+        let mut pieces = Vec::new();
+        for color in [Color::White, Color::Black] {
+            for piece in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen, PieceType::King] {
+                pieces.push(((piece, color), load(&piece_dir.join(render::piece_file_name(piece, color)))?));
+            }
+        }
+        Ok(Raster { pixmap, pieces })
+    }
+
+    // An image saved before, without any pieces to draw
+    pub fn open_png(path: &Path) -> Result<Raster, String> {
+        Ok(Raster { pixmap: load(path)?, pieces: Vec::new() })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixmap.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixmap.height()
+    }
+
+    // The color of a pixel as rgba, None outside of the image
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        let c = self.pixmap.pixel(x, y)?.demultiply();
+        Some([c.red(), c.green(), c.blue(), c.alpha()])
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), String> {
+        self.pixmap.save_png(path).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+// Draws `view` into a new image just large enough for the board
+pub fn render(view: &BoardView, piece_dir: &Path) -> Result<Raster, String> {
+    let mut raster = Raster::new((view.cell * 8.0).round() as u32, piece_dir)?;
+    render::paint(view, &mut raster);
+    Ok(raster)
+}
+
+impl Painter for Raster {
+    fn fill_rect(&mut self, rect: Rect, color: Rgba) {
+        if let Some(r) = tiny_skia::Rect::from_xywh(rect.x, rect.y, rect.w, rect.h) {
+            self.pixmap.fill_rect(r, &paint(color), Transform::identity(), None);
+        }
+    }
+
+    fn stroke_rect(&mut self, rect: Rect, width: f32, color: Rgba) {
+        if let Some(r) = tiny_skia::Rect::from_xywh(rect.x, rect.y, rect.w, rect.h) {
+            let path = PathBuilder::from_rect(r);
+            self.pixmap.stroke_path(&path, &paint(color), &stroke(width), Transform::identity(), None);
+        }
+    }
+
+    fn fill_circle(&mut self, center: Point, radius: f32, color: Rgba) {
+        if let Some(path) = PathBuilder::from_circle(center.0, center.1, radius) {
+            self.pixmap.fill_path(&path, &paint(color), FillRule::Winding, Transform::identity(), None);
+        }
+    }
+
+    fn stroke_circle(&mut self, center: Point, radius: f32, width: f32, color: Rgba) {
+        if let Some(path) = PathBuilder::from_circle(center.0, center.1, radius) {
+            self.pixmap.stroke_path(&path, &paint(color), &stroke(width), Transform::identity(), None);
+        }
+    }
+
+    fn line(&mut self, from: Point, to: Point, width: f32, color: Rgba) {
+        let mut pb = PathBuilder::new();
+        pb.move_to(from.0, from.1);
+        pb.line_to(to.0, to.1);
+        if let Some(path) = pb.finish() {
+            self.pixmap.stroke_path(&path, &paint(color), &stroke(width), Transform::identity(), None);
+        }
+    }
+
+    fn fill_polygon(&mut self, points: &[Point], color: Rgba) {
+        let mut pb = PathBuilder::new();
+        for (i, p) in points.iter().enumerate() {
+            if i == 0 {
+                pb.move_to(p.0, p.1);
+            } else {
+                pb.line_to(p.0, p.1);
+            }
+        }
+        pb.close();
+        if let Some(path) = pb.finish() {
+            self.pixmap.fill_path(&path, &paint(color), FillRule::Winding, Transform::identity(), None);
+        }
+    }
+
+    fn piece(&mut self, piece: PieceType, color: Color, square: Rect) {
+        let Raster { pixmap, pieces } = self;
+        if let Some((_, image)) = pieces.iter().find(|(p, _)| *p == (piece, color)) {
+            let scale = Transform::from_row(square.w / image.width() as f32, 0.0, 0.0, square.h / image.height() as f32, square.x, square.y);
+            let paint = PixmapPaint { quality: FilterQuality::Bilinear, ..PixmapPaint::default() };
+            pixmap.draw_pixmap(0, 0, image.as_ref(), &paint, scale, None);
+        }
+    }
+}
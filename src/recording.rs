@@ -0,0 +1,238 @@
+use crate::networking::{c2s_message, s2c_message, C2sMessage, PlayerIdentity, S2cMessage};
+use crate::notation::*;
+use crate::session::{unix_time, Event, Session};
+use crate::transport::{self, Channel, Transport};
+use prost::Message;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// A recording is a text file with a header line followed by one message per line:
+// "<milliseconds since the start> <direction> <message as hex> <message as text>"
+const HEADER: &str = "# chess-gui session";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Host,
+    Client,
+    Spectator,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Host => "host",
+            Role::Client => "client",
+            Role::Spectator => "spectator",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Role> {
+        match s {
+            "host" => Some(Role::Host),
+            "client" => Some(Role::Client),
+            "spectator" => Some(Role::Spectator),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Sent => ">",
+            Direction::Received => "<",
+        }
+    }
+}
+
+// Recordings are meant to be shared, e.g. with a bug report, so the password and the reconnect
+// token of whoever connects are blanked out before a message is written
+pub trait Redact: Message + Clone {
+    fn redact(&mut self) {}
+}
+
+impl Redact for S2cMessage {}
+
+impl Redact for C2sMessage {
+    fn redact(&mut self) {
+        let identity = match &mut self.msg {
+            Some(c2s_message::Msg::ConnectRequest(r)) => r.identity.as_mut(),
+            Some(c2s_message::Msg::CreateGame(r)) => r.identity.as_mut(),
+            Some(c2s_message::Msg::JoinGame(r)) => r.identity.as_mut(),
+            _ => None,
+        };
+        if let Some(identity) = identity {
+            identity.password = None;
+            identity.token.clear();
+        }
+    }
+}
+
+// Writes every message of a session to a file as it is sent or received
+pub struct Recorder {
+    file: std::io::LineWriter<std::fs::File>,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, role: Role) -> std::io::Result<Recorder> {
+        let mut file = std::io::LineWriter::new(std::fs::File::create(path)?);
+        writeln!(file, "{} {} started at {}", HEADER, role.as_str(), unix_time())?;
+        Ok(Recorder { file, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, direction: Direction, message: &impl Redact) {
+        let mut message = message.clone();
+        message.redact();
+        let hex: String = message.encode_to_vec().iter().map(|b| format!("{:02x}", b)).collect();
+        // A recording that can't be written isn't worth ending the game over
+        let _ = writeln!(
+            self.file,
+            "{} {} {} {:?}",
+            self.start.elapsed().as_millis(),
+            direction.as_str(),
+            hex,
+            message
+        );
+    }
+}
+
+pub struct Record {
+    pub at: Duration,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+pub struct Recording {
+    pub role: Role,
+    pub records: Vec<Record>,
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn invalid(line: usize) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Line {} of the recording is malformed", line + 1))
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> std::io::Result<Recording> {
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        let mut lines = file.lines();
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let role = header
+            .strip_prefix(HEADER)
+            .and_then(|rest| rest.split_whitespace().next())
+            .and_then(Role::parse)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Not a session recording"))?;
+
+        let mut records = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let mut fields = line.splitn(4, ' ');
+            let at = fields.next().and_then(|f| f.parse().ok()).ok_or_else(|| invalid(i + 1))?;
+            let direction = match fields.next() {
+                Some(">") => Direction::Sent,
+                Some("<") => Direction::Received,
+                _ => return Err(invalid(i + 1)),
+            };
+            let data = fields.next().and_then(parse_hex).ok_or_else(|| invalid(i + 1))?;
+            records.push(Record { at: Duration::from_millis(at), direction, data });
+        }
+        Ok(Recording { role, records })
+    }
+}
+
+// Plays a recording back into a session in the same order and at the same pace as it was
+// recorded. The session is connected to the replay instead of a peer: received messages are
+// passed to it and our own moves and chat are made again.
+pub struct Replay {
+    recording: Recording,
+    next: usize,
+    start: Instant,
+    peer: Channel,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> (Replay, Session) {
+        let (peer, ours) = transport::channel();
+        let identity = PlayerIdentity::default();
+        let session = match recording.role {
+            Role::Host => Session::host_over(ours, identity),
+            Role::Client => Session::client(ours, false, identity),
+            Role::Spectator => Session::client(ours, true, identity),
+        };
+        let replay = Replay {
+            recording,
+            next: 0,
+            start: Instant::now(),
+            peer,
+        };
+        (replay, session)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.records.len()
+    }
+
+    // Replays everything that is due, but stops after a received message so the session can
+    // handle it before our next move is made. Our own chat messages are returned as events since
+    // the session doesn't report what it sends.
+    pub fn step(&mut self, session: &mut Session) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(record) = self.recording.records.get(self.next) {
+            if record.at > self.start.elapsed() {
+                break;
+            }
+            self.next += 1;
+            match record.direction {
+                Direction::Received => {
+                    // The session only hangs up on us when it is dropped
+                    let _ = self.peer.send_message(&record.data);
+                    break;
+                }
+                Direction::Sent => self.resend(record, session, &mut events),
+            }
+        }
+        // What the session sends has been recorded already
+        while let Ok(Some(_)) = self.peer.receive_message() {}
+        events
+    }
+
+    fn resend(&self, record: &Record, session: &mut Session, events: &mut Vec<Event>) {
+        let (m, chat) = if self.recording.role == Role::Host {
+            match S2cMessage::decode(record.data.as_slice()).ok().and_then(|m| m.msg) {
+                Some(s2c_message::Msg::Move(m)) => (Some(m), None),
+                Some(s2c_message::Msg::Chat(c)) => (None, Some(c)),
+                _ => (None, None),
+            }
+        } else {
+            match C2sMessage::decode(record.data.as_slice()).ok().and_then(|m| m.msg) {
+                Some(c2s_message::Msg::Move(m)) => (Some(m), None),
+                Some(c2s_message::Msg::Chat(c)) => (None, Some(c)),
+                _ => (None, None),
+            }
+        };
+        if let Some(m) = m {
+            session.make_move(square_to_pos(m.from_square), square_to_pos(m.to_square), promotion_from_proto(m.promotion));
+            events.push(Event::Moved);
+        }
+        if let Some(chat) = chat {
+            events.push(Event::Chat(chat));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use crate::annotations::Annotation;
+use crate::networking::AnnotationColor;
+use crate::settings::Theme;
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+
+// Everything on the board is laid out here and handed to a painter, so the board looks the same
+// in the window and when it is drawn without one, e.g. in the visual tests.
+pub type Point = (f32, f32);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+// Components from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Rgba {
+        Rgba { r, g, b, a }
+    }
+
+    pub fn from_rgb((r, g, b): (u8, u8, u8)) -> Rgba {
+        Rgba::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    }
+}
+
+const SELECTED_COLOR: Rgba = Rgba::new(0.0, 85.0 / 255.0, 71.0 / 255.0, 1.0);
+const HIGHLIGHT_COLOR: Rgba = Rgba::new(94.0 / 255.0, 74.0 / 255.0, 130.0 / 255.0, 1.0);
+// Marked squares are drawn over the pieces so the color has to be see-through
+const MARKED_COLOR: Rgba = Rgba::new(0.8, 0.2, 0.2, 0.45);
+// The outlines drawn instead of the usual highlights for players who need them larger, in cells
+const OUTLINE_WIDTH: f32 = 12.0 / 140.0;
+const OUTLINE_COLOR: Rgba = Rgba::new(1.0, 0.85, 0.0, 1.0);
+
+// What draws the shapes. Shapes are painted in the order they are given, later ones on top.
+pub trait Painter {
+    fn fill_rect(&mut self, rect: Rect, color: Rgba);
+    // The stroke is centered on the edge of `rect`
+    fn stroke_rect(&mut self, rect: Rect, width: f32, color: Rgba);
+    fn fill_circle(&mut self, center: Point, radius: f32, color: Rgba);
+    fn stroke_circle(&mut self, center: Point, radius: f32, width: f32, color: Rgba);
+    fn line(&mut self, from: Point, to: Point, width: f32, color: Rgba);
+    fn fill_polygon(&mut self, points: &[Point], color: Rgba);
+    // The image of a piece, stretched over `square`
+    fn piece(&mut self, piece: PieceType, color: Color, square: Rect);
+}
+
+// A position and what is shown on top of it
+pub struct BoardView<'a> {
+    pub board: &'a Board,
+    // Wether black is drawn at the bottom
+    pub flipped: bool,
+    pub selected: Option<Pos>,
+    // Where the selected piece can go
+    pub highlights: &'a [Pos],
+    // Squares covered with a see-through color, e.g. queued premoves
+    pub marked: &'a [Pos],
+    pub annotations: &'a [Annotation],
+    pub theme: Theme,
+    pub large_outlines: bool,
+    // How many pixels wide and high a square is
+    pub cell: f32,
+}
+
+impl<'a> BoardView<'a> {
+    // Only the pieces, without anything on top of them
+    pub fn plain(board: &'a Board, flipped: bool, theme: Theme, cell: f32) -> BoardView<'a> {
+        BoardView {
+            board,
+            flipped,
+            selected: None,
+            highlights: &[],
+            marked: &[],
+            annotations: &[],
+            theme,
+            large_outlines: false,
+            cell,
+        }
+    }
+
+    fn square(&self, pos: Pos) -> Rect {
+        let at = flip(pos, self.flipped);
+        Rect { x: at.x as f32 * self.cell, y: at.y as f32 * self.cell, w: self.cell, h: self.cell }
+    }
+
+    fn center(&self, pos: Pos) -> Point {
+        let at = flip(pos, self.flipped);
+        ((at.x as f32 + 0.5) * self.cell, (at.y as f32 + 0.5) * self.cell)
+    }
+}
+
+// Turns a square on the board into the square it is drawn at and back. With the board flipped
+// black is at the bottom.
+pub fn flip(pos: Pos, flipped: bool) -> Pos {
+    if flipped {
+        Pos { x: 7 - pos.x, y: 7 - pos.y }
+    } else {
+        pos
+    }
+}
+
+// The image of a piece in a piece set, e.g. w_king.png
+pub fn piece_file_name(piece: PieceType, color: Color) -> String {
+    let name = match piece {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    };
+    format!("{}_{}.png", if color == Color::White { "w" } else { "b" }, name)
+}
+
+// Annotations are drawn over the pieces so their colors have to be see-through
+fn annotation_color(color: AnnotationColor) -> Rgba {
+    match color {
+        AnnotationColor::Green => Rgba::new(0.1, 0.6, 0.2, 0.7),
+        AnnotationColor::Red => Rgba::new(0.8, 0.1, 0.1, 0.7),
+        AnnotationColor::Blue => Rgba::new(0.1, 0.4, 0.8, 0.7),
+        AnnotationColor::Yellow => Rgba::new(0.9, 0.7, 0.0, 0.7),
+    }
+}
+
+// Paints the squares, the selected square, the pieces, the squares the selected piece can go to,
+// the marked squares and the annotations, in that order
+pub fn paint(view: &BoardView, painter: &mut impl Painter) {
+    let cell = view.cell;
+    let (light, dark) = view.theme.colors();
+    for y in 0..8i8 {
+        for x in 0..8i8 {
+            let color = if (x + y) % 2 == 0 { light } else { dark };
+            painter.fill_rect(view.square(Pos { x, y }), Rgba::from_rgb(color));
+        }
+    }
+
+    if let Some(selected) = view.selected {
+        let square = view.square(selected);
+        if view.large_outlines {
+            // The outline is drawn inside the square so it doesn't cover the neighbours
+            let width = OUTLINE_WIDTH * cell;
+            let inside = Rect { x: square.x + width / 2.0, y: square.y + width / 2.0, w: square.w - width, h: square.h - width };
+            painter.stroke_rect(inside, width, OUTLINE_COLOR);
+        } else {
+            painter.fill_rect(square, SELECTED_COLOR);
+        }
+    }
+
+    for y in 0..8i8 {
+        for x in 0..8i8 {
+            if let Some(p) = &view.board.board[y as usize][x as usize] {
+                painter.piece(p.get_type(), p.get_color(), view.square(Pos { x, y }));
+            }
+        }
+    }
+
+    for &pos in view.highlights {
+        let middle = view.center(pos);
+        if view.large_outlines {
+            // A dark dot in a light ring stands out on both colors of square and on the pieces
+            painter.fill_circle(middle, cell / 5.0, OUTLINE_COLOR);
+            painter.fill_circle(middle, cell / 8.0, Rgba::BLACK);
+        } else {
+            painter.fill_circle(middle, cell / 10.0, HIGHLIGHT_COLOR);
+        }
+    }
+
+    for &pos in view.marked {
+        painter.fill_rect(view.square(pos), MARKED_COLOR);
+    }
+
+    let width = cell / 8.0;
+    for a in view.annotations {
+        let color = annotation_color(a.color);
+        let (from, to) = (view.center(a.from), view.center(a.to));
+        if a.is_circle() {
+            painter.stroke_circle(from, cell / 2.0 - width / 2.0, width / 2.0, color);
+            continue;
+        }
+        // The line stops where the head starts so the see-through colors don't overlap
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        let (dx, dy) = (dx / length, dy / length);
+        let head = (to.0 - dx * width * 2.0, to.1 - dy * width * 2.0);
+        let side = (-dy * width * 1.5, dx * width * 1.5);
+        painter.line(from, head, width, color);
+        painter.fill_polygon(&[to, (head.0 + side.0, head.1 + side.1), (head.0 - side.0, head.1 - side.1)], color);
+    }
+}
@@ -0,0 +1,135 @@
+use chess::board::Board;
+use chess_gui::accessibility;
+use chess_gui::pgn::Pgn;
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+
+use crate::board_view::draw_board;
+use crate::export::{export_path, report, save_gif, save_png};
+use crate::scene::{Scene, Shared, Transition};
+use crate::utils::*;
+
+const LINE_HEIGHT: f32 = 24.0;
+const MARGIN: f32 = 12.0;
+// Room for the players and the help above the moves
+const MOVES_TOP: f32 = PLAYERS_HEIGHT as f32 + 48.0;
+
+// Steps through a finished game, or one loaded from a PGN file, one move at a time
+pub struct ReviewScreen {
+    pgn: Pgn,
+    // The position before the first move and after every move
+    positions: Vec<Board>,
+    // How many moves have been made in the position shown
+    ply: usize,
+}
+
+impl ReviewScreen {
+    // Fails if one of the moves isn't legal, the review starts at the end of the game
+    pub fn new(pgn: Pgn) -> Result<ReviewScreen, String> {
+        let positions = pgn.boards()?;
+        let ply = positions.len() - 1;
+        Ok(ReviewScreen { pgn, positions, ply })
+    }
+
+    // Moves to another position and says which move led to it
+    fn go_to(&mut self, shared: &Shared, ply: usize) {
+        self.ply = ply.min(self.positions.len() - 1);
+        shared.announce(&match self.ply.checked_sub(1) {
+            Some(i) => format!("{}{} {}", i / 2 + 1, if i % 2 == 0 { "." } else { "..." }, self.pgn.moves[i]),
+            None => "Start of the game".to_string(),
+        });
+    }
+
+    // The moves two to a line with the last move that was made marked, scrolled so that it is
+    // always visible
+    fn draw_moves(&self, canvas: &mut Canvas) {
+        let left = SCREEN_DIMENSIONS.0 as f32 + MARGIN;
+        let rows = ((SCREEN_DIMENSIONS.1 as f32 - MOVES_TOP - MARGIN) / LINE_HEIGHT) as usize;
+        let lines: Vec<String> = self
+            .pgn
+            .moves
+            .chunks(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let mark = |j: usize| if i * 2 + j + 1 == self.ply { "*" } else { " " };
+                let black = pair.get(1).map_or(String::new(), |m| format!("{}{}", mark(1), m));
+                format!("{:>3}. {}{:<8} {}", i + 1, mark(0), pair[0], black)
+            })
+            .collect();
+        let current = self.ply.saturating_sub(1) / 2;
+        let first = (current + 1).saturating_sub(rows);
+        for (i, line) in lines.iter().skip(first).take(rows).enumerate() {
+            let text = graphics::Text::new(line.as_str());
+            let dst = Vec2::new(left, MOVES_TOP + i as f32 * LINE_HEIGHT);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::WHITE));
+        }
+    }
+}
+
+impl Scene for ReviewScreen {
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared) {
+        let flipped = shared.settings.orientation.is_flipped(None);
+        draw_board(ctx, canvas, shared, &self.positions[self.ply], flipped);
+
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: SCREEN_DIMENSIONS.0 as f32, y: 0.0, w: PANEL_WIDTH as f32, h: SCREEN_DIMENSIONS.1 as f32 },
+            Color::from_rgb(30, 30, 30)).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        let left = SCREEN_DIMENSIONS.0 as f32 + MARGIN;
+        let players = format!(
+            "{} - {}  {}",
+            self.pgn.tag("White").unwrap_or("?"),
+            self.pgn.tag("Black").unwrap_or("?"),
+            self.pgn.result.as_deref().unwrap_or("*")
+        );
+        let lines = [players, "Left/Right: step, Home/End: jump".to_string(), "F5: describe the position, F6/F7: save it/the game".to_string(), "Escape: back".to_string()];
+        for (i, line) in lines.into_iter().enumerate() {
+            let text = graphics::Text::new(line);
+            let dst = Vec2::new(left, MARGIN + i as f32 * LINE_HEIGHT);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::WHITE));
+        }
+        self.draw_moves(canvas);
+    }
+
+    // Clicking the left or right half of the board steps back or forward
+    fn mouse_down(&mut self, _ctx: &mut Context, shared: &mut Shared, _button: MouseButton, x: f32, y: f32) -> Transition {
+        if square_at(x, y).is_some() {
+            if x < SCREEN_DIMENSIONS.0 as f32 / 2.0 {
+                self.go_to(shared, self.ply.saturating_sub(1));
+            } else {
+                self.go_to(shared, self.ply + 1);
+            }
+        }
+        Transition::Stay
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, keycode: KeyCode) -> Transition {
+        match keycode {
+            KeyCode::Left => self.go_to(shared, self.ply.saturating_sub(1)),
+            KeyCode::Right => self.go_to(shared, self.ply + 1),
+            KeyCode::Home => self.go_to(shared, 0),
+            KeyCode::End => self.go_to(shared, usize::MAX),
+            KeyCode::F5 => shared.announce(&accessibility::describe_position(&self.positions[self.ply])),
+            KeyCode::F6 => {
+                let (path, flipped, size) = (export_path("png"), shared.settings.orientation.is_flipped(None), shared.settings.export_size);
+                let board = &self.positions[self.ply];
+                let result = save_png(ctx, size, &path, |ctx, canvas| draw_board(ctx, canvas, shared, board, flipped));
+                report(shared, result.map(|()| path));
+            }
+            KeyCode::F7 => {
+                let (path, flipped, settings) = (export_path("gif"), shared.settings.orientation.is_flipped(None), &shared.settings);
+                let result = save_gif(ctx, shared, &self.positions, flipped, settings.export_size, settings.gif_delay, &path);
+                report(shared, result.map(|()| path));
+            }
+            KeyCode::Escape => return Transition::Pop,
+            _ => (),
+        }
+        Transition::Stay
+    }
+}
@@ -0,0 +1,80 @@
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+
+// How a game ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    // The color that gave mate
+    Checkmate(Color),
+    Stalemate,
+}
+
+impl Outcome {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Outcome::Checkmate(Color::White) => "Checkmate, white wins",
+            Outcome::Checkmate(Color::Black) => "Checkmate, black wins",
+            Outcome::Stalemate => "Stalemate",
+        }
+    }
+
+    // The result as written in PGN
+    pub fn result(self) -> &'static str {
+        match self {
+            Outcome::Checkmate(Color::White) => "1-0",
+            Outcome::Checkmate(Color::Black) => "0-1",
+            Outcome::Stalemate => "1/2-1/2",
+        }
+    }
+}
+
+fn opponent(color: Color) -> Color {
+    if color == Color::White { Color::Black } else { Color::White }
+}
+
+// Every piece of `color` and where it stands
+pub fn pieces_of(board: &Board, color: Color) -> Vec<(Pos, PieceType)> {
+    let mut pieces = Vec::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            if let Some(p) = &board.board[y][x] {
+                if p.get_color() == color {
+                    pieces.push((Pos { x: x as i8, y: y as i8 }, p.get_type()));
+                }
+            }
+        }
+    }
+    pieces
+}
+
+// Every move the side to move can make, as (from, to)
+pub fn legal_moves(board: &Board) -> Vec<(Pos, Pos)> {
+    pieces_of(board, board.turn)
+        .into_iter()
+        .flat_map(|(from, _)| board.get_possible_moves_at_square(from).into_iter().map(move |to| (from, to)))
+        .collect()
+}
+
+// Wether a piece of the other side could take the king of `color`
+pub fn is_in_check(board: &Board, color: Color) -> bool {
+    let king = match pieces_of(board, color).into_iter().find(|&(_, t)| t == PieceType::King) {
+        Some((pos, _)) => pos,
+        None => return false,
+    };
+    pieces_of(board, opponent(color))
+        .into_iter()
+        .any(|(from, _)| board.get_possible_moves_at_square(from).contains(&king))
+}
+
+// None while the side to move has a move left
+pub fn outcome(board: &Board) -> Option<Outcome> {
+    if !legal_moves(board).is_empty() {
+        return None;
+    }
+    if is_in_check(board, board.turn) {
+        Some(Outcome::Checkmate(opponent(board.turn)))
+    } else {
+        Some(Outcome::Stalemate)
+    }
+}
@@ -0,0 +1,106 @@
+use chess_gui::accessibility;
+use chess_gui::settings::{self, Settings};
+use chess_gui::sound::Sound;
+use ggez::event::MouseButton;
+use ggez::graphics::{self, Canvas, Color};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+
+use crate::sounds::Sounds;
+use crate::utils::*;
+
+// What the app should do with the scenes after an event
+pub enum Transition {
+    Stay,
+    // Shows another scene on top of this one, e.g. the settings
+    Push(Box<dyn Scene>),
+    // Swaps this scene for another, e.g. the lobby for the game that was joined
+    Replace(Box<dyn Scene>),
+    // Goes back to the scene underneath
+    Pop,
+}
+
+// One screen of the app. Only the scene on top of the stack is updated, drawn and given input.
+pub trait Scene {
+    fn update(&mut self, _ctx: &mut Context, _shared: &mut Shared) -> Transition {
+        Transition::Stay
+    }
+
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, shared: &Shared);
+
+    fn mouse_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, _button: MouseButton, _x: f32, _y: f32) -> Transition {
+        Transition::Stay
+    }
+
+    fn mouse_up(&mut self, _ctx: &mut Context, _shared: &mut Shared, _button: MouseButton, _x: f32, _y: f32) -> Transition {
+        Transition::Stay
+    }
+
+    fn key_down(&mut self, _ctx: &mut Context, _shared: &mut Shared, _keycode: KeyCode) -> Transition {
+        Transition::Stay
+    }
+
+    // Wether F2 opens the settings on top of this scene
+    fn opens_settings(&self) -> bool {
+        true
+    }
+
+    // Only printable characters are passed on
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, _character: char) -> Transition {
+        Transition::Stay
+    }
+}
+
+// What every scene may use
+pub struct Shared {
+    pub settings: Settings,
+    pub pieces: Imglib,
+    pub sounds: Sounds,
+    // Shown at the bottom of the window, e.g. why the connection was refused
+    pub message: Option<String>,
+    // The options from the command line that connections made from the menu use too, e.g. --tls
+    pub options: Vec<String>,
+}
+
+impl Shared {
+    // Starts using the settings the player changed and saves them for next time
+    pub fn apply_settings(&mut self, ctx: &mut Context, settings: Settings) {
+        if settings.piece_set != self.settings.piece_set {
+            match Imglib::new(ctx, &settings.piece_set) {
+                Ok(pieces) => self.pieces = pieces,
+                Err(e) => {
+                    self.message = Some(format!("Failed to load the piece set {}: {}", settings.piece_set, e));
+                    return;
+                }
+            }
+        }
+        if settings.sound_pack != self.settings.sound_pack {
+            self.sounds = Sounds::new(ctx, &settings.sound_pack);
+        }
+        if let Err(e) = settings.save(&settings::default_path()) {
+            self.message = Some(format!("Failed to save the settings: {}", e));
+        }
+        self.settings = settings;
+    }
+
+    // Plays a sound unless the player turned them off
+    pub fn play(&mut self, ctx: &Context, sound: Sound) {
+        if self.settings.sound {
+            self.sounds.play(ctx, sound, self.settings.volume);
+        }
+    }
+
+    // Tells players who can't see the board what happened, if they asked for it
+    pub fn announce(&self, text: &str) {
+        accessibility::announce(&self.settings, text);
+    }
+
+    pub fn draw_message(&self, canvas: &mut Canvas) {
+        if let Some(message) = &self.message {
+            let text = graphics::Text::new(message.as_str());
+            let dst = Vec2::new(8.0, SCREEN_DIMENSIONS.1 as f32 - 32.0);
+            canvas.draw(&text, graphics::DrawParam::new().dest(dst).color(Color::RED));
+        }
+    }
+}
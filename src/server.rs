@@ -0,0 +1,540 @@
+// The standalone server that hosts many games at once and pairs players through the lobby
+use crate::handshake::*;
+use crate::identity::*;
+use crate::logging::{self, NETWORKING};
+use crate::networking::{self, c2s_message, s2c_message, Capability, ChatChannel, ColorPreference, PlayerIdentity, S2cMessage, PROTOCOL_VERSION};
+use crate::notation::*;
+use crate::session::system_message;
+use crate::stream::{self, Accepting};
+use crate::transport::HostConnection;
+use chess::board::Board;
+use chess::util::Color;
+use log::{debug, info, warn};
+use prost::Message;
+use std::collections::HashMap;
+use rustls::ServerConfig;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::Duration;
+
+// How long the server sleeps between polling its connections
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+struct Game {
+    board: Board,
+    white: Option<usize>,
+    black: Option<usize>,
+    // Who took each seat. They stay after a player disconnects so that only the same player can
+    // take the seat back.
+    white_player: Option<PlayerIdentity>,
+    black_player: Option<PlayerIdentity>,
+    spectators: Vec<usize>,
+    creator_name: String,
+    time_control: Option<networking::TimeControl>,
+    color_preference: ColorPreference,
+    password: Option<String>,
+}
+
+impl Game {
+    fn new() -> Game {
+        Game {
+            board: Board::new(),
+            white: None,
+            black: None,
+            white_player: None,
+            black_player: None,
+            spectators: Vec::new(),
+            creator_name: String::new(),
+            time_control: None,
+            color_preference: ColorPreference::Random,
+            password: None,
+        }
+    }
+
+    // A game is open while it is waiting for its second player
+    fn is_open(&self) -> bool {
+        self.white_player.is_some() != self.black_player.is_some()
+    }
+
+    // Seats a player, returns wether they got the white pieces
+    fn seat(&mut self, client: usize, identity: PlayerIdentity) -> Result<bool, String> {
+        let seats = [(&self.white_player, self.white.is_some()), (&self.black_player, self.black.is_some())];
+        let white = pick_seat(&seats, &Some(identity.clone()))? == 0;
+        if white {
+            self.white = Some(client);
+            self.white_player = Some(identity);
+        } else {
+            self.black = Some(client);
+            self.black_player = Some(identity);
+        }
+        Ok(white)
+    }
+
+    fn players(&self) -> networking::S2cPlayers {
+        networking::S2cPlayers {
+            white_name: self.white_player.is_some().then(|| name_of(&self.white_player)),
+            black_name: self.black_player.is_some().then(|| name_of(&self.black_player)),
+        }
+    }
+
+    fn color_of(&self, client: usize) -> Option<Color> {
+        if self.white == Some(client) {
+            Some(Color::White)
+        } else if self.black == Some(client) {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    // Everyone in the game except `client`
+    fn others(&self, client: usize) -> Vec<usize> {
+        self.white
+            .iter()
+            .chain(self.black.iter())
+            .chain(self.spectators.iter())
+            .copied()
+            .filter(|&c| c != client)
+            .collect()
+    }
+
+    fn player_name(&self, client: usize) -> String {
+        match self.color_of(client) {
+            Some(Color::White) => name_of(&self.white_player),
+            Some(Color::Black) => name_of(&self.black_player),
+            None => "A spectator".to_string(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.white.is_none() && self.black.is_none() && self.spectators.is_empty()
+    }
+}
+
+struct Client {
+    connection: HostConnection,
+    // The game the client has joined, set once its connect request has been accepted
+    game_id: Option<u64>,
+    // The optional features both the server and the client support
+    capabilities: Vec<Capability>,
+}
+
+pub struct Server {
+    listener: TcpListener,
+    // Set when clients have to connect with TLS
+    tls: Option<Arc<ServerConfig>>,
+    // Connections that are still doing their TLS or WebSocket handshake
+    accepting: Vec<(SocketAddr, Accepting)>,
+    clients: HashMap<usize, Client>,
+    games: HashMap<u64, Game>,
+    next_client_id: usize,
+    next_game_id: u64,
+}
+
+impl Server {
+    // `listener` has to be non blocking
+    pub fn new(listener: TcpListener, tls: Option<Arc<ServerConfig>>) -> Server {
+        Server {
+            listener,
+            tls,
+            accepting: Vec::new(),
+            clients: HashMap::new(),
+            games: HashMap::new(),
+            next_client_id: 0,
+            next_game_id: 1,
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.poll();
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    // Accepts new connections and handles what every client has sent since the last poll
+    pub fn poll(&mut self) {
+        self.accept_connections();
+        let ids: Vec<usize> = self.clients.keys().copied().collect();
+        for id in ids {
+            self.poll_client(id);
+        }
+    }
+
+    fn accept_connections(&mut self) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    info!(target: NETWORKING, "Accepted connection from {}", addr);
+                    match Accepting::new(stream, self.tls.as_ref()) {
+                        Ok(accepting) => self.accepting.push((addr, accepting)),
+                        Err(e) => warn!(target: NETWORKING, "Handshake with {} failed: {}", addr, e),
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                // Whoever was trying to connect can try again
+                Err(e) => {
+                    warn!(target: NETWORKING, "Failed to accept a connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let mut ready = Vec::new();
+        self.accepting.retain_mut(|(addr, accepting)| match accepting.poll() {
+            Ok(Some(stream)) => {
+                ready.push(HostConnection::new(stream));
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                warn!(target: NETWORKING, "Handshake with {} failed: {}", addr, e);
+                false
+            }
+        });
+        for connection in ready {
+            self.clients.insert(self.next_client_id, Client { connection, game_id: None, capabilities: Vec::new() });
+            self.next_client_id += 1;
+        }
+    }
+
+    fn poll_client(&mut self, id: usize) {
+        // The client may have been dropped while polling someone else
+        let client = match self.clients.get_mut(&id) {
+            Some(c) => c,
+            None => return,
+        };
+        // Everything logged while handling the client is about its game
+        logging::set_game_id(client.game_id);
+        let packet = match client.connection.poll() {
+            Ok(p) => p,
+            Err(e) => {
+                info!(target: NETWORKING, "Client {} disconnected: {}", id, e);
+                self.disconnect(id);
+                return;
+            }
+        };
+        match packet.and_then(|p| p.msg) {
+            Some(c2s_message::Msg::ConnectRequest(r)) => self.handle_connect(id, r),
+            Some(c2s_message::Msg::Move(m)) => self.handle_move(id, m),
+            Some(c2s_message::Msg::ListGames(_)) => self.handle_list_games(id),
+            Some(c2s_message::Msg::CreateGame(c)) => self.handle_create_game(id, c),
+            Some(c2s_message::Msg::JoinGame(j)) => self.handle_join_game(id, j),
+            Some(c2s_message::Msg::Chat(m)) => self.handle_chat(id, m),
+            Some(c2s_message::Msg::Annotations(a)) => self.handle_annotations(id, a),
+            None => (),
+        }
+    }
+
+    fn send(&mut self, id: usize, msg: s2c_message::Msg) {
+        let data = S2cMessage { msg: Some(msg) };
+        let failed = match self.clients.get_mut(&id) {
+            Some(c) => c.connection.send(&data).is_err(),
+            None => false,
+        };
+        if failed {
+            self.disconnect(id);
+        }
+    }
+
+    fn handle_connect(&mut self, id: usize, request: networking::C2sConnectRequest) {
+        let game_id = request.game_id;
+        let identity = request.identity.unwrap_or_default();
+        let result = self.handshake(id, request.protocol_version, &request.capabilities).and_then(|_| {
+            if request.spectate {
+                match self.games.get_mut(&game_id) {
+                    Some(game) => {
+                        check_password(&game.password, &Some(identity))?;
+                        game.spectators.push(id);
+                        Ok(None)
+                    }
+                    None => Err(format!("There is no game {} to spectate", game_id)),
+                }
+            } else {
+                // Clients that don't use the lobby create a game by asking for an unused id. The
+                // first player to ask for it plays white, sets its password and the second one
+                // plays black.
+                let game = self.games.entry(game_id).or_insert_with(|| Game {
+                    creator_name: name_of(&Some(identity.clone())),
+                    password: identity.password.clone(),
+                    ..Game::new()
+                });
+                check_password(&game.password, &Some(identity.clone()))?;
+                game.seat(id, identity).map(Some)
+            }
+        });
+        self.acknowledge(id, game_id, result);
+    }
+
+    fn handle_list_games(&mut self, id: usize) {
+        let mut games: Vec<networking::GameInfo> = self
+            .games
+            .iter()
+            .filter(|(_, g)| g.is_open())
+            .map(|(&game_id, g)| networking::GameInfo {
+                game_id,
+                creator_name: g.creator_name.clone(),
+                time_control: g.time_control.clone(),
+                color_preference: g.color_preference as i32,
+                has_password: g.password.is_some(),
+            })
+            .collect();
+        games.sort_by_key(|g| g.game_id);
+        // The games waiting the longest are listed if they don't all fit into one read
+        let mut list = networking::S2cGameList { games };
+        let too_long = |list: &networking::S2cGameList| S2cMessage { msg: Some(s2c_message::Msg::GameList(list.clone())) }.encoded_len() > stream::READ_SIZE;
+        while too_long(&list) {
+            list.games.pop();
+        }
+        self.send(id, s2c_message::Msg::GameList(list));
+    }
+
+    fn handle_create_game(&mut self, id: usize, request: networking::C2sCreateGame) {
+        while self.games.contains_key(&self.next_game_id) {
+            self.next_game_id += 1;
+        }
+        let game_id = self.next_game_id;
+        if let Err(e) = self.handshake(id, request.protocol_version, &request.capabilities) {
+            self.acknowledge(id, game_id, Err(e));
+            return;
+        }
+
+        // Older clients only send their name
+        let identity = request.identity.unwrap_or_else(|| PlayerIdentity {
+            name: request.creator_name,
+            ..Default::default()
+        });
+        let color_preference =
+            ColorPreference::from_i32(request.color_preference).unwrap_or(ColorPreference::Random);
+        let creator_is_white = match color_preference {
+            ColorPreference::White => true,
+            ColorPreference::Black => false,
+            ColorPreference::Random => random_bool(),
+        };
+        let mut game = Game::new();
+        game.creator_name = name_of(&Some(identity.clone()));
+        game.password = identity.password.clone();
+        if creator_is_white {
+            game.white = Some(id);
+            game.white_player = Some(identity);
+        } else {
+            game.black = Some(id);
+            game.black_player = Some(identity);
+        }
+        game.time_control = request.time_control;
+        game.color_preference = color_preference;
+        self.games.insert(game_id, game);
+        logging::set_game_id(Some(game_id));
+        info!("Client {} created game {}", id, game_id);
+
+        self.acknowledge(id, game_id, Ok(Some(creator_is_white)));
+    }
+
+    fn handle_join_game(&mut self, id: usize, request: networking::C2sJoinGame) {
+        let game_id = request.game_id;
+        // Older clients only send their name
+        let identity = request.identity.unwrap_or_else(|| PlayerIdentity {
+            name: request.player_name,
+            ..Default::default()
+        });
+        let name = name_of(&Some(identity.clone()));
+        let result = self.handshake(id, request.protocol_version, &request.capabilities).and_then(|_| {
+            match self.games.get_mut(&game_id) {
+                Some(game) => {
+                    check_password(&game.password, &Some(identity.clone()))?;
+                    game.seat(id, identity).map(Some)
+                }
+                None => Err(format!("There is no game {}", game_id)),
+            }
+        });
+        if result.is_ok() {
+            info!("{} joined game {}", name, game_id);
+        }
+        self.acknowledge(id, game_id, result);
+    }
+
+    // Checks the protocol version and capabilities sent with every way of joining a game
+    fn handshake(&mut self, id: usize, version: i32, capabilities: &[i32]) -> Result<(), String> {
+        let client = self.clients.get_mut(&id).unwrap();
+        if client.game_id.is_some() {
+            return Err("Already playing or watching a game".to_string());
+        }
+        check_version(version)?;
+        client.capabilities = negotiate(capabilities);
+        Ok(())
+    }
+
+    // Sends the connect ack that answers every way of joining a game. On success the result says
+    // wether the client plays white, None for spectators.
+    fn acknowledge(&mut self, id: usize, game_id: u64, result: Result<Option<bool>, String>) {
+        if result.is_ok() {
+            self.clients.get_mut(&id).unwrap().game_id = Some(game_id);
+            logging::set_game_id(Some(game_id));
+            debug!("Client {} joined game {}", id, game_id);
+        }
+        let starting_position = self.games.get(&game_id).map(|g| networking::BoardState {
+            fen_string: board_to_fen(&g.board),
+        });
+        let players = self.games.get(&game_id).map(|g| g.players()).unwrap_or_default();
+        let capabilities = self.clients.get(&id).map_or(Vec::new(), |c| c.capabilities.iter().map(|&c| c as i32).collect());
+        let (success, client_is_white, error) = match result {
+            Ok(white) => (true, white, None),
+            Err(e) => (false, None, Some(e)),
+        };
+        self.send(
+            id,
+            s2c_message::Msg::ConnectAck(networking::S2cConnectAck {
+                success,
+                game_id: Some(game_id),
+                starting_position,
+                client_is_white,
+                protocol_version: PROTOCOL_VERSION as i32,
+                capabilities,
+                error,
+                white_name: players.white_name.clone(),
+                black_name: players.black_name.clone(),
+            }),
+        );
+        if success {
+            let who = match client_is_white {
+                Some(white) => {
+                    // Everyone else learns who took the seat
+                    let others = self.games.get(&game_id).map_or(Vec::new(), |g| g.others(id));
+                    for other in others {
+                        self.send(other, s2c_message::Msg::Players(players.clone()));
+                    }
+                    let name = self.games.get(&game_id).map_or(String::new(), |g| g.player_name(id));
+                    format!("{} ({})", name, if white { "white" } else { "black" })
+                }
+                None => "A spectator".to_string(),
+            };
+            self.announce(game_id, format!("{} joined the game", who));
+        }
+    }
+
+    fn handle_move(&mut self, id: usize, m: networking::Move) {
+        let game_id = match self.clients.get(&id).and_then(|c| c.game_id) {
+            Some(g) => g,
+            None => return,
+        };
+        let game = self.games.get_mut(&game_id).unwrap();
+
+        let from = square_to_pos(m.from_square);
+        let to = square_to_pos(m.to_square);
+        let legal = m.from_square < 64
+            && m.to_square < 64
+            && game.white.is_some()
+            && game.black.is_some()
+            && game.color_of(id) == Some(game.board.turn)
+            && game.board.get_possible_moves_at_square(from).contains(&to)
+            && game
+                .board
+                .perform_move(from, to, promotion_from_proto(m.promotion))
+                .is_ok();
+
+        let board_result = Some(networking::BoardState {
+            fen_string: board_to_fen(&game.board),
+        });
+        let others = game.others(id);
+        self.send(id, s2c_message::Msg::MoveAck(networking::S2cMoveAck { legal, board_result }));
+
+        if legal {
+            for other in others {
+                self.send(other, s2c_message::Msg::Move(m.clone()));
+            }
+        }
+    }
+
+    // What a player draws is shown to everyone else in the game who can show it
+    fn handle_annotations(&mut self, id: usize, annotations: networking::Annotations) {
+        let game = match self.clients.get(&id).and_then(|c| c.game_id).and_then(|g| self.games.get(&g)) {
+            Some(g) => g,
+            None => return,
+        };
+        // Spectators only get to look
+        if game.spectators.contains(&id) {
+            return;
+        }
+        let recipients: Vec<usize> = game
+            .others(id)
+            .into_iter()
+            .filter(|r| self.clients.get(r).is_some_and(|c| c.capabilities.contains(&Capability::Annotations)))
+            .collect();
+        for r in recipients {
+            self.send(r, s2c_message::Msg::Annotations(annotations.clone()));
+        }
+    }
+
+    // Player chat goes to the other player and spectator chat to the other spectators
+    fn handle_chat(&mut self, id: usize, mut message: networking::ChatMessage) {
+        let game = match self.clients.get(&id).and_then(|c| c.game_id).and_then(|g| self.games.get(&g)) {
+            Some(g) => g,
+            None => return,
+        };
+        let recipients: Vec<usize> = if game.spectators.contains(&id) {
+            message.channel = ChatChannel::Spectators as i32;
+            game.spectators.iter().copied().filter(|&s| s != id).collect()
+        } else {
+            message.channel = ChatChannel::Players as i32;
+            game.white.iter().chain(game.black.iter()).copied().filter(|&p| p != id).collect()
+        };
+        // Nobody gets to pretend to be the server
+        message.system = false;
+        self.send_chat(&recipients, message);
+    }
+
+    fn send_chat(&mut self, recipients: &[usize], message: networking::ChatMessage) {
+        for &r in recipients {
+            if self.clients.get(&r).is_some_and(|c| c.capabilities.contains(&Capability::Chat)) {
+                self.send(r, s2c_message::Msg::Chat(message.clone()));
+            }
+        }
+    }
+
+    // Tells the players of a game that something happened
+    fn announce(&mut self, game_id: u64, text: String) {
+        let players: Vec<usize> = match self.games.get(&game_id) {
+            Some(g) => g.white.iter().chain(g.black.iter()).copied().collect(),
+            None => return,
+        };
+        self.send_chat(&players, system_message(text, ChatChannel::Players));
+    }
+
+    fn disconnect(&mut self, id: usize) {
+        let client = match self.clients.remove(&id) {
+            Some(c) => c,
+            None => return,
+        };
+        let game_id = match client.game_id {
+            Some(g) => g,
+            None => return,
+        };
+        let mut left = None;
+        if let Some(game) = self.games.get_mut(&game_id) {
+            if game.color_of(id).is_some() {
+                left = Some(game.player_name(id));
+            }
+            if game.white == Some(id) {
+                game.white = None;
+            }
+            if game.black == Some(id) {
+                game.black = None;
+            }
+            game.spectators.retain(|&s| s != id);
+            if game.is_empty() {
+                info!("Game {} is over", game_id);
+                self.games.remove(&game_id);
+            }
+        }
+        if let Some(who) = left {
+            self.announce(game_id, format!("{} left the game", who));
+        }
+    }
+}
+
+// Good enough to pick a side for players who don't care which color they get
+fn random_bool() -> bool {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .is_ok_and(|d| d.subsec_nanos() % 2 == 0)
+}
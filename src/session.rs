@@ -0,0 +1,760 @@
+use crate::annotations::Annotation;
+use crate::error::Error;
+use crate::handshake::*;
+use crate::identity::*;
+use crate::logging::{self, NETWORKING};
+use crate::networking::{self, c2s_message, s2c_message, C2sMessage, Capability, ChatChannel, ChatMessage, PlayerIdentity, S2cMessage, PROTOCOL_VERSION};
+use crate::notation::*;
+use crate::pgn::Pgn;
+use crate::polyglot;
+use crate::recording::{Direction, Recorder, Redact, Role};
+use chess::board::Board;
+use chess::piece::PieceType;
+use chess::util::{Color, Pos};
+use crate::stream::Accepting;
+use crate::tls;
+use crate::transport::{ClientConnection, HostConnection, Transport};
+use rustls::ServerConfig;
+use std::net::{SocketAddr, TcpListener};
+use log::{debug, info, warn};
+use std::sync::Arc;
+
+// Things that happened while polling the network that the GUI may want to react to
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    // The opponent made a move, or a move was reported to us as a spectator
+    Moved,
+    // The server answered our connect, create or join request
+    Connected { success: bool },
+    // The server didn't accept our last move and sent us its board instead
+    MoveRejected,
+    GameList(Vec<networking::GameInfo>),
+    Disconnected,
+    // Something the player should be told about, like the peer speaking another protocol version
+    Error(String),
+    Chat(ChatMessage),
+    // Everything the other player has drawn on the board now
+    Annotations(Vec<Annotation>),
+}
+
+// A move in the history of a game
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayedMove {
+    // In standard algebraic notation, as it was written before the move was made
+    pub san: String,
+    pub from: Pos,
+    pub to: Pos,
+    pub promotion: Option<PieceType>,
+}
+
+impl PlayedMove {
+    pub fn uci(&self) -> String {
+        move_to_uci(self.from, self.to, self.promotion)
+    }
+}
+
+// Someone watching a game we host
+struct Spectator {
+    connection: HostConnection,
+    capabilities: Vec<Capability>,
+}
+
+// Seconds since the unix epoch, the timestamp used in chat messages
+pub fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+// A chat line about the game itself, e.g. someone joining
+pub fn system_message(text: impl Into<String>, channel: ChatChannel) -> ChatMessage {
+    ChatMessage {
+        sender: String::new(),
+        text: text.into(),
+        channel: channel as i32,
+        sent_at: unix_time(),
+        system: true,
+    }
+}
+
+// The networked part of a game: the board and everyone we are playing with. The GUI drives it
+// by calling `poll` every frame and `make_move` when the player moves a piece.
+pub struct Session {
+    pub board: Board,
+    // Our connection to the host when we are a client
+    host: Option<ClientConnection>,
+    // The host's connection to the opponent
+    opponent: Option<HostConnection>,
+    listener: Option<TcpListener>,
+    // Set when connections to the listener have to use TLS
+    tls: Option<Arc<ServerConfig>>,
+    // Connections to the listener that are still doing their TLS or WebSocket handshake
+    accepting: Vec<(SocketAddr, Accepting)>,
+    // Connections that haven't sent a connect request yet
+    pending: Vec<HostConnection>,
+    spectators: Vec<Spectator>,
+    pub is_client: bool,
+    pub is_spectator: bool,
+    // The color we are playing, None if we may move both sides
+    pub color: Option<Color>,
+    // The optional features both we and the opponent support
+    pub capabilities: Vec<Capability>,
+    // The TLS certificate fingerprint players should compare: the host's own or the one the host
+    // showed us
+    pub fingerprint: Option<String>,
+    // Who we are. The host's password is the one everyone joining has to give.
+    pub identity: PlayerIdentity,
+    // The host remembers who took the opponent's seat so only they can come back to it
+    opponent_identity: Option<PlayerIdentity>,
+    pub white_name: Option<String>,
+    pub black_name: Option<String>,
+    // Set while the messages to and from the host or the opponent are written to a file
+    recorder: Option<Recorder>,
+    // What went wrong outside of `poll`, reported by the next call to it
+    errors: Vec<Error>,
+    // The moves made so far and the position before the first
+    pub history: Vec<PlayedMove>,
+    pub start_fen: String,
+}
+
+impl Session {
+    // A game that isn't connected to anyone
+    pub fn new() -> Session {
+        Session {
+            board: Board::new(),
+            host: None,
+            opponent: None,
+            listener: None,
+            tls: None,
+            accepting: Vec::new(),
+            pending: Vec::new(),
+            spectators: Vec::new(),
+            is_client: false,
+            is_spectator: false,
+            color: None,
+            capabilities: Vec::new(),
+            fingerprint: None,
+            identity: PlayerIdentity::default(),
+            opponent_identity: None,
+            white_name: None,
+            black_name: None,
+            recorder: None,
+            errors: Vec::new(),
+            history: Vec::new(),
+            start_fen: START_FEN.to_string(),
+        }
+    }
+
+    // Hosts a game on a non blocking listener. The host plays white.
+    pub fn host(listener: TcpListener, identity: PlayerIdentity) -> Session {
+        Session {
+            listener: Some(listener),
+            color: Some(Color::White),
+            white_name: Some(name_of(&Some(identity.clone()))),
+            identity,
+            ..Session::new()
+        }
+    }
+
+    // Like `host` but clients have to connect with TLS using our certificate
+    pub fn host_tls(listener: TcpListener, identity: PlayerIdentity, certificate: &tls::Identity) -> std::io::Result<Session> {
+        Ok(Session {
+            tls: Some(certificate.server_config()?),
+            fingerprint: Some(certificate.fingerprint()),
+            ..Session::host(listener, identity)
+        })
+    }
+
+    // Hosts a game for a single peer on the other end of `transport`, e.g. an in memory channel
+    pub fn host_over(transport: impl Transport + 'static, identity: PlayerIdentity) -> Session {
+        Session {
+            color: Some(Color::White),
+            white_name: Some(name_of(&Some(identity.clone()))),
+            identity,
+            pending: vec![HostConnection::new(transport)],
+            ..Session::new()
+        }
+    }
+
+    fn connected_to(transport: impl Transport + 'static, identity: PlayerIdentity) -> Session {
+        let host = ClientConnection::new(transport);
+        Session {
+            fingerprint: host.fingerprint(),
+            host: Some(host),
+            is_client: true,
+            identity,
+            ..Session::new()
+        }
+    }
+
+    // Joins the game on the other end of a non blocking transport, as a player or as a spectator
+    pub fn client(transport: impl Transport + 'static, spectate: bool, identity: PlayerIdentity) -> Session {
+        let mut s = Session {
+            is_spectator: spectate,
+            ..Session::connected_to(transport, identity.clone())
+        };
+        s.send_to_host(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+            game_id: 0,
+            spectate,
+            protocol_version: PROTOCOL_VERSION as i32,
+            capabilities: supported_capabilities(),
+            identity: Some(identity),
+        }));
+        s
+    }
+
+    // Connects to a game server without joining a game. The game is picked with `create_game` or
+    // `join_game`.
+    pub fn lobby(transport: impl Transport + 'static, identity: PlayerIdentity) -> Session {
+        let mut s = Session::connected_to(transport, identity);
+        s.list_games();
+        s
+    }
+
+    pub fn list_games(&mut self) {
+        self.send_to_host(c2s_message::Msg::ListGames(networking::C2sListGames {}));
+    }
+
+    // Our password, if any, is the one the game is created with
+    pub fn create_game(&mut self, time_control: networking::TimeControl, preference: networking::ColorPreference) {
+        self.send_to_host(c2s_message::Msg::CreateGame(networking::C2sCreateGame {
+            creator_name: self.name(),
+            time_control: Some(time_control),
+            color_preference: preference as i32,
+            protocol_version: PROTOCOL_VERSION as i32,
+            capabilities: supported_capabilities(),
+            identity: Some(self.identity.clone()),
+        }));
+    }
+
+    pub fn join_game(&mut self, game_id: u64) {
+        self.send_to_host(c2s_message::Msg::JoinGame(networking::C2sJoinGame {
+            game_id,
+            player_name: self.name(),
+            protocol_version: PROTOCOL_VERSION as i32,
+            capabilities: supported_capabilities(),
+            identity: Some(self.identity.clone()),
+        }));
+    }
+
+    pub fn name(&self) -> String {
+        name_of(&Some(self.identity.clone()))
+    }
+
+    // The name of whoever plays `color`, None while no one has taken the seat
+    pub fn player_name(&self, color: Color) -> Option<&str> {
+        match color {
+            Color::White => self.white_name.as_deref(),
+            Color::Black => self.black_name.as_deref(),
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        match (self.is_client, self.is_spectator) {
+            (false, _) => Role::Host,
+            (true, false) => Role::Client,
+            (true, true) => Role::Spectator,
+        }
+    }
+
+    // Writes every message exchanged with the host or the opponent from now on to `path`, see
+    // `recording::Replay` for playing it back
+    pub fn start_recording(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        self.recorder = Some(Recorder::create(path, self.role())?);
+        Ok(())
+    }
+
+    fn record(&mut self, direction: Direction, message: &impl Redact) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(direction, message);
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.host.is_some() || self.opponent.is_some()
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    // Players and spectators chat separately
+    pub fn chat_channel(&self) -> ChatChannel {
+        if self.is_spectator { ChatChannel::Spectators } else { ChatChannel::Players }
+    }
+
+    // Sends a chat message to everyone in our channel. The message is returned so that it can be
+    // shown to the sender as well, None means no one we talk to supports chat.
+    pub fn send_chat(&mut self, text: String) -> Option<ChatMessage> {
+        if !self.supports(Capability::Chat) {
+            return None;
+        }
+        let message = ChatMessage {
+            sender: self.name(),
+            text,
+            channel: self.chat_channel() as i32,
+            sent_at: unix_time(),
+            system: false,
+        };
+        if self.is_client {
+            self.send_to_host(c2s_message::Msg::Chat(message.clone()));
+        } else {
+            // The spectators don't get to read the players' chat
+            self.send_to_opponent(&S2cMessage { msg: Some(s2c_message::Msg::Chat(message.clone())) });
+        }
+        Some(message)
+    }
+
+    // Shows what we have drawn on the board to everyone who can show it. A host may be teaching
+    // spectators without an opponent so they are asked separately.
+    pub fn share_annotations(&mut self, annotations: &[Annotation]) {
+        if self.is_spectator {
+            return;
+        }
+        let annotations = networking::Annotations { annotations: annotations.iter().map(|a| a.to_proto()).collect() };
+        let supported = self.supports(Capability::Annotations);
+        if self.is_client {
+            if supported {
+                self.send_to_host(c2s_message::Msg::Annotations(annotations));
+            }
+            return;
+        }
+        if supported {
+            self.send_to_opponent(&S2cMessage { msg: Some(s2c_message::Msg::Annotations(annotations.clone())) });
+        }
+        self.annotations_to_spectators(annotations);
+    }
+
+    fn annotations_to_spectators(&mut self, annotations: networking::Annotations) {
+        let data = S2cMessage { msg: Some(s2c_message::Msg::Annotations(annotations)) };
+        self.spectators
+            .retain_mut(|s| !s.capabilities.contains(&Capability::Annotations) || s.connection.send(&data).is_ok());
+    }
+
+    pub fn spectator_count(&self) -> usize {
+        self.spectators.len()
+    }
+
+    // Wether the piece at `pos` may be picked up by the player
+    pub fn can_move_piece_at(&self, pos: Pos) -> bool {
+        if self.is_spectator {
+            return false;
+        }
+        if !(0..8).contains(&pos.x) || !(0..8).contains(&pos.y) {
+            return false;
+        }
+        match &self.board.board[pos.y as usize][pos.x as usize] {
+            Some(p) => p.get_color() == self.board.turn && self.color.is_none_or(|c| c == p.get_color()),
+            None => false,
+        }
+    }
+
+    // Performs a move on our board and sends it to the opponent. A move the board doesn't allow
+    // isn't sent and is reported by the next `poll`.
+    pub fn make_move(&mut self, from: Pos, to: Pos, promotion: Option<PieceType>) {
+        if !self.apply_move(from, to, promotion) {
+            warn!("Our own move {}{} was refused by the board", pos_name(from), pos_name(to));
+            self.errors.push(Error::Input(format!("{}{} isn't a legal move", pos_name(from), pos_name(to))));
+            return;
+        }
+
+        let m = networking::Move {
+            from_square: pos_to_square(from),
+            to_square: pos_to_square(to),
+            promotion: promotion_to_proto(promotion),
+        };
+        if self.is_client {
+            debug!(target: NETWORKING, "Sending move {}{} to the host", pos_name(from), pos_name(to));
+            self.send_to_host(c2s_message::Msg::Move(m));
+        } else {
+            debug!(target: NETWORKING, "Sending move {}{} to the opponent", pos_name(from), pos_name(to));
+            let data = S2cMessage { msg: Some(s2c_message::Msg::Move(m)) };
+            self.send_to_opponent(&data);
+            self.broadcast_to_spectators(&data);
+        }
+    }
+
+    // Performs a move on the board and adds it to the history if the board allows it
+    fn apply_move(&mut self, from: Pos, to: Pos, promotion: Option<PieceType>) -> bool {
+        // Neither the notation nor the board expect squares off the board
+        let on_board = |p: Pos| (0..8).contains(&p.x) && (0..8).contains(&p.y);
+        if !on_board(from) || !on_board(to) {
+            return false;
+        }
+        let san = move_to_san(&self.board, from, to, promotion);
+        if self.board.perform_move(from, to, promotion).is_err() {
+            return false;
+        }
+        self.history.push(PlayedMove { san, from, to, promotion });
+        true
+    }
+
+    // Starts the history over from the current position
+    fn reset_history(&mut self) {
+        self.history.clear();
+        self.start_fen = board_to_fen(&self.board);
+        if self.start_fen == board_to_fen(&Board::new()) {
+            self.start_fen = START_FEN.to_string();
+        }
+    }
+
+    // The game so far, e.g. to review or save it
+    pub fn pgn(&self) -> Pgn {
+        let mut tags = Vec::new();
+        let names = [("White", &self.white_name), ("Black", &self.black_name)];
+        for (tag, name) in names {
+            tags.push((tag.to_string(), name.clone().unwrap_or_else(|| "?".to_string())));
+        }
+        if self.start_fen != START_FEN {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), self.start_fen.clone()));
+        }
+        let result = crate::rules::outcome(&self.board).map(|o| o.result().to_string());
+        Pgn { tags, moves: self.history.iter().map(|m| m.san.clone()).collect(), result }
+    }
+
+    // The key an opening book knows the current position by
+    pub fn polyglot_key(&self) -> u64 {
+        let castling = self.start_fen.split_whitespace().nth(2).unwrap_or("-");
+        polyglot::key(&self.board, &polyglot::castling_rights(castling, &self.history), self.history.last())
+    }
+
+    // A connection that can't be sent to is dropped, the next `poll` reports it as lost
+    fn send_to_host(&mut self, msg: c2s_message::Msg) {
+        let data = C2sMessage { msg: Some(msg) };
+        if let Some(host) = &mut self.host {
+            match host.send(&data) {
+                Ok(()) => self.record(Direction::Sent, &data),
+                Err(e) => {
+                    self.host = None;
+                    self.errors.push(e);
+                }
+            }
+        }
+    }
+
+    fn send_to_opponent(&mut self, data: &S2cMessage) {
+        if let Some(opponent) = &mut self.opponent {
+            match opponent.send(data) {
+                Ok(()) => self.record(Direction::Sent, data),
+                Err(e) => {
+                    self.opponent = None;
+                    self.errors.push(e);
+                }
+            }
+        }
+    }
+
+    // A lost connection is reported and dropped, the events say what happened
+    fn lost_connection(e: Error, events: &mut Vec<Event>) {
+        warn!(target: NETWORKING, "Lost connection: {}", e);
+        if e.is_notable() {
+            events.push(Event::Error(e.to_string()));
+        }
+        events.push(Event::Disconnected);
+    }
+
+    // Spectators that have hung up are dropped from the list
+    fn broadcast_to_spectators(&mut self, data: &S2cMessage) {
+        self.spectators.retain_mut(|s| s.connection.send(data).is_ok());
+    }
+
+    // Passes spectator chat on to the other spectators. The host is a player so it isn't shown.
+    fn poll_spectators(&mut self) {
+        let mut messages = Vec::new();
+        self.spectators.retain_mut(|s| match s.connection.poll() {
+            Ok(Some(C2sMessage { msg: Some(c2s_message::Msg::Chat(mut m)) })) => {
+                m.channel = ChatChannel::Spectators as i32;
+                messages.push(m);
+                true
+            }
+            Ok(_) => true,
+            Err(_) => false,
+        });
+        for m in messages {
+            let data = S2cMessage { msg: Some(s2c_message::Msg::Chat(m)) };
+            self.spectators
+                .retain_mut(|s| !s.capabilities.contains(&Capability::Chat) || s.connection.send(&data).is_ok());
+        }
+    }
+
+    // Handles everything that has arrived since the last call
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        for e in std::mem::take(&mut self.errors) {
+            match e {
+                // Only network errors cost us the connection
+                Error::Input(_) => events.push(Event::Error(e.to_string())),
+                e => Session::lost_connection(e, &mut events),
+            }
+        }
+        if let Some(host) = &mut self.host {
+            match host.poll() {
+                Ok(Some(data)) => {
+                    self.record(Direction::Received, &data);
+                    if let Some(msg) = data.msg {
+                        self.handle_s2c(msg, &mut events);
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    self.host = None;
+                    Session::lost_connection(e, &mut events);
+                }
+            }
+        }
+        if !self.is_client {
+            self.accept_connections(&mut events);
+            self.poll_spectators();
+        }
+        if let Some(opponent) = &mut self.opponent {
+            match opponent.poll() {
+                Ok(Some(data)) => {
+                    self.record(Direction::Received, &data);
+                    if let Some(msg) = data.msg {
+                        self.handle_c2s(msg, &mut events);
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => {
+                    self.opponent = None;
+                    Session::lost_connection(e, &mut events);
+                }
+            }
+        }
+        events
+    }
+
+    fn handle_s2c(&mut self, msg: s2c_message::Msg, events: &mut Vec<Event>) {
+        match msg {
+            s2c_message::Msg::Move(m) => {
+                let p = square_to_pos(m.from_square);
+                let pos = square_to_pos(m.to_square);
+                if m.from_square >= 64 || m.to_square >= 64 {
+                    warn!(target: NETWORKING, "The move from {} to {} we were sent isn't on the board", m.from_square, m.to_square);
+                    return;
+                }
+                if !self.apply_move(p, pos, promotion_from_proto(m.promotion)) {
+                    warn!(target: NETWORKING, "The move {}{} we were sent isn't legal on our board", pos_name(p), pos_name(pos));
+                    return;
+                }
+                debug!(target: NETWORKING, "Received move {}{}", pos_name(p), pos_name(pos));
+                events.push(Event::Moved);
+            }
+            s2c_message::Msg::ConnectAck(ca) => {
+                debug!(target: NETWORKING, "Received connect ack, success: {}", ca.success);
+                if !ca.success {
+                    if let Some(error) = ca.error {
+                        events.push(Event::Error(error));
+                    }
+                    events.push(Event::Connected { success: false });
+                    return;
+                }
+                if let Err(error) = check_version(ca.protocol_version) {
+                    // Nothing the peer sends from now on can be trusted to mean what we think
+                    self.host = None;
+                    events.push(Event::Error(error));
+                    events.push(Event::Connected { success: false });
+                    return;
+                }
+                self.capabilities = negotiate(&ca.capabilities);
+                if let Some(board) = ca.starting_position.and_then(|b| board_from_fen(&b.fen_string)) {
+                    self.board = board;
+                }
+                self.reset_history();
+                self.color = ca.client_is_white.map(|white| if white { Color::White } else { Color::Black });
+                self.white_name = ca.white_name;
+                self.black_name = ca.black_name;
+                logging::set_game_id(ca.game_id);
+                info!(target: NETWORKING, "Joined the game as {:?}", self.role());
+                events.push(Event::Connected { success: true });
+            }
+            s2c_message::Msg::MoveAck(ma) => {
+                debug!(target: NETWORKING, "Received move ack, legal: {}", ma.legal);
+                // The server didn't accept our move so we go back to its board
+                if !ma.legal {
+                    if let Some(board) = ma.board_result.and_then(|b| board_from_fen(&b.fen_string)) {
+                        self.board = board;
+                    }
+                    self.history.pop();
+                    events.push(Event::MoveRejected);
+                }
+            }
+            s2c_message::Msg::GameList(list) => events.push(Event::GameList(list.games)),
+            s2c_message::Msg::Chat(m) => events.push(Event::Chat(m)),
+            s2c_message::Msg::Players(p) => {
+                self.white_name = p.white_name;
+                self.black_name = p.black_name;
+            }
+            s2c_message::Msg::Annotations(a) => {
+                events.push(Event::Annotations(a.annotations.iter().filter_map(Annotation::from_proto).collect()));
+            }
+        }
+    }
+
+    fn handle_c2s(&mut self, msg: c2s_message::Msg, events: &mut Vec<Event>) {
+        match msg {
+            c2s_message::Msg::Move(m) => {
+                let p = square_to_pos(m.from_square);
+                let pos = square_to_pos(m.to_square);
+                // The board only checks that the piece may move there, not wether it is theirs
+                let legal = if self.color == Some(self.board.turn) {
+                    warn!(target: NETWORKING, "The opponent sent the move {}{} while it is our turn", pos_name(p), pos_name(pos));
+                    false
+                } else if m.from_square >= 64 || m.to_square >= 64 {
+                    warn!(target: NETWORKING, "The move from {} to {} we were sent isn't on the board", m.from_square, m.to_square);
+                    false
+                } else if !self.apply_move(p, pos, promotion_from_proto(m.promotion)) {
+                    warn!(target: NETWORKING, "The move {}{} we were sent isn't legal on our board", pos_name(p), pos_name(pos));
+                    false
+                } else {
+                    true
+                };
+                if !legal {
+                    // The opponent goes back to our board, the spectators never see the move
+                    let board_result = Some(networking::BoardState { fen_string: board_to_fen(&self.board) });
+                    let ack = networking::S2cMoveAck { legal: false, board_result };
+                    self.send_to_opponent(&S2cMessage { msg: Some(s2c_message::Msg::MoveAck(ack)) });
+                    return;
+                }
+                debug!(target: NETWORKING, "Received move {}{}", pos_name(p), pos_name(pos));
+                self.broadcast_to_spectators(&S2cMessage { msg: Some(s2c_message::Msg::Move(m)) });
+                events.push(Event::Moved);
+            }
+            c2s_message::Msg::ConnectRequest(_) => {
+                debug!(target: NETWORKING, "Ignoring a connect request from the connected opponent");
+            }
+            c2s_message::Msg::Chat(mut m) => {
+                m.channel = ChatChannel::Players as i32;
+                events.push(Event::Chat(m));
+            }
+            // The lobby is only served by the standalone server
+            c2s_message::Msg::Annotations(a) => {
+                events.push(Event::Annotations(a.annotations.iter().filter_map(Annotation::from_proto).collect()));
+                // The spectators see what both players draw
+                self.annotations_to_spectators(a);
+            }
+            c2s_message::Msg::ListGames(_) | c2s_message::Msg::CreateGame(_) | c2s_message::Msg::JoinGame(_) => (),
+        }
+    }
+
+    // Accepts new connections and answers their connect requests. The first player to connect
+    // becomes the opponent, everyone asking to spectate gets the current position and is sent
+    // every move from then on.
+    fn accept_connections(&mut self, events: &mut Vec<Event>) {
+        while let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, addr)) => {
+                    info!(target: NETWORKING, "Accepted connection from {}", addr);
+                    match Accepting::new(stream, self.tls.as_ref()) {
+                        Ok(accepting) => self.accepting.push((addr, accepting)),
+                        Err(e) => warn!(target: NETWORKING, "Handshake with {} failed: {}", addr, e),
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                // Whoever was trying to connect can try again
+                Err(e) => {
+                    warn!(target: NETWORKING, "Failed to accept a connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let pending = &mut self.pending;
+        self.accepting.retain_mut(|(addr, accepting)| match accepting.poll() {
+            Ok(Some(stream)) => {
+                pending.push(HostConnection::new(stream));
+                false
+            }
+            Ok(None) => true,
+            Err(e) => {
+                warn!(target: NETWORKING, "Handshake with {} failed: {}", addr, e);
+                false
+            }
+        });
+
+        let mut still_pending = Vec::new();
+        for mut connection in std::mem::take(&mut self.pending) {
+            let request = match connection.poll() {
+                Ok(Some(C2sMessage { msg: Some(c2s_message::Msg::ConnectRequest(r)) })) => r,
+                Ok(Some(_)) => continue,
+                Ok(None) => {
+                    still_pending.push(connection);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+
+            let error = check_version(request.protocol_version)
+                .and_then(|_| check_password(&self.identity.password, &request.identity))
+                .and_then(|_| {
+                    if request.spectate {
+                        Ok(0)
+                    } else {
+                        pick_seat(&[(&self.opponent_identity, self.opponent.is_some())], &request.identity)
+                    }
+                })
+                .err();
+            let accepted = error.is_none();
+            let capabilities = negotiate(&request.capabilities);
+            let returning = self.opponent_identity.is_some();
+            if accepted && !request.spectate {
+                self.black_name = Some(name_of(&request.identity));
+                // Peers without an identity still take the seat but can't come back to it
+                self.opponent_identity = Some(request.identity.clone().unwrap_or_default());
+            }
+            let ack = S2cMessage {
+                msg: Some(s2c_message::Msg::ConnectAck(networking::S2cConnectAck {
+                    success: accepted,
+                    game_id: Some(request.game_id),
+                    starting_position: Some(networking::BoardState {
+                        fen_string: board_to_fen(&self.board),
+                    }),
+                    client_is_white: if request.spectate { None } else { Some(false) },
+                    protocol_version: PROTOCOL_VERSION as i32,
+                    capabilities: capabilities.iter().map(|&c| c as i32).collect(),
+                    error: error.clone(),
+                    white_name: self.white_name.clone(),
+                    black_name: self.black_name.clone(),
+                })),
+            };
+            if let Some(error) = &error {
+                info!(target: NETWORKING, "Refused {}: {}", name_of(&request.identity), error);
+            }
+            if connection.send(&ack).is_err() || !accepted {
+                continue;
+            }
+            if !request.spectate {
+                let data = C2sMessage { msg: Some(c2s_message::Msg::ConnectRequest(request.clone())) };
+                self.record(Direction::Received, &data);
+                self.record(Direction::Sent, &ack);
+            }
+
+            if request.spectate {
+                info!(target: NETWORKING, "Spectator joined");
+                self.spectators.push(Spectator { connection, capabilities });
+                events.push(Event::Chat(system_message("A spectator joined", ChatChannel::Players)));
+            } else {
+                info!(target: NETWORKING, "{} joined as the opponent", name_of(&request.identity));
+                self.opponent = Some(connection);
+                self.capabilities = capabilities;
+                let name = name_of(&request.identity);
+                let text = if returning { format!("{} reconnected", name) } else { format!("{} joined", name) };
+                events.push(Event::Chat(system_message(text, ChatChannel::Players)));
+                self.broadcast_to_spectators(&S2cMessage {
+                    msg: Some(s2c_message::Msg::Players(networking::S2cPlayers {
+                        white_name: self.white_name.clone(),
+                        black_name: self.black_name.clone(),
+                    })),
+                });
+            }
+        }
+        self.pending = still_pending;
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
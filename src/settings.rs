@@ -0,0 +1,286 @@
+use crate::error::{Error, Result};
+use crate::networking;
+use chess::util::Color;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Where the settings are kept, next to the TLS certificate and the player token
+pub fn default_path() -> PathBuf {
+    crate::tls::default_dir().join("settings.toml")
+}
+
+// The colours of the light and dark squares
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Green,
+    Brown,
+    Blue,
+    Gray,
+    // White and black squares for players who can't tell the others apart well
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 5] = [Theme::Green, Theme::Brown, Theme::Blue, Theme::Gray, Theme::HighContrast];
+
+    // (light, dark) as rgb
+    pub fn colors(self) -> ((u8, u8, u8), (u8, u8, u8)) {
+        match self {
+            Theme::Green => ((238, 238, 210), (118, 150, 86)),
+            Theme::Brown => ((240, 217, 181), (181, 136, 99)),
+            Theme::Blue => ((222, 227, 230), (140, 162, 173)),
+            Theme::Gray => ((220, 220, 220), (130, 130, 130)),
+            Theme::HighContrast => ((255, 255, 255), (60, 60, 60)),
+        }
+    }
+}
+
+// Which side of the board is at the bottom of the window
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    // The color we play, white when we may move both sides
+    Auto,
+    White,
+    Black,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 3] = [Orientation::Auto, Orientation::White, Orientation::Black];
+
+    // Wether black is at the bottom when we play `color`
+    pub fn is_flipped(self, color: Option<Color>) -> bool {
+        match self {
+            Orientation::Auto => color == Some(Color::Black),
+            Orientation::White => false,
+            Orientation::Black => true,
+        }
+    }
+}
+
+// How moves and game events are announced for players who can't see the board
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Announcements {
+    Off,
+    // Written to stdout, for a screen reader following the terminal
+    Print,
+    // Read out by the speech command
+    Speech,
+}
+
+impl Announcements {
+    pub const ALL: [Announcements; 3] = [Announcements::Off, Announcements::Print, Announcements::Speech];
+}
+
+// The time control of the games we create
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub minutes: u32,
+    pub increment: u32,
+}
+
+impl TimeControl {
+    pub fn to_proto(self) -> networking::TimeControl {
+        networking::TimeControl {
+            initial_seconds: self.minutes * 60,
+            increment_seconds: self.increment,
+        }
+    }
+}
+
+// Everything the player can change without recompiling. Missing fields get their default so
+// older settings files keep working.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // The address we host on and connect to when no other is given
+    pub host: String,
+    pub port: u16,
+    pub name: String,
+    pub theme: Theme,
+    // A directory of piece images in the resources, empty for the default pieces
+    pub piece_set: String,
+    pub sound: bool,
+    // In percent
+    pub volume: u8,
+    // A directory of sounds in the resources' sounds directory, empty for the default sounds
+    pub sound_pack: String,
+    pub orientation: Orientation,
+    // A UCI engine to play against
+    pub engine_path: Option<PathBuf>,
+    // A Polyglot opening book the engine plays from while the game is in it
+    pub book_path: Option<PathBuf>,
+    // Puzzles in the CSV format of the Lichess puzzle database
+    pub puzzle_path: Option<PathBuf>,
+    // Looked in for images and sounds before the bundled resources
+    pub resource_dir: Option<PathBuf>,
+    // Wether the arrows and circles we draw are shown to the opponent and spectators
+    pub share_annotations: bool,
+    pub announcements: Announcements,
+    // Run with the text to read out, e.g. "spd-say" from speech-dispatcher
+    pub speech_command: String,
+    // Thick outlines around the selected square and the squares it can go to
+    pub large_outlines: bool,
+    // How many pixels wide and high exported screenshots and games are
+    pub export_size: u32,
+    // How long every position of an exported game is shown, in milliseconds
+    pub gif_delay: u32,
+    // Last since TOML wants tables after all plain values
+    pub time_control: TimeControl,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            host: "127.0.0.1".to_string(),
+            port: 1337,
+            name: crate::identity::ANONYMOUS.to_string(),
+            theme: Theme::Green,
+            piece_set: String::new(),
+            sound: true,
+            volume: 80,
+            sound_pack: String::new(),
+            orientation: Orientation::Auto,
+            engine_path: None,
+            book_path: None,
+            puzzle_path: None,
+            resource_dir: None,
+            share_annotations: false,
+            announcements: Announcements::Off,
+            speech_command: "spd-say".to_string(),
+            large_outlines: false,
+            export_size: 480,
+            gif_delay: 800,
+            time_control: TimeControl { minutes: 10, increment: 0 },
+        }
+    }
+}
+
+// Removes `--flag value` from the arguments and returns the value
+pub fn take_option(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == flag)?;
+    args.remove(i);
+    (i < args.len()).then(|| args.remove(i))
+}
+
+fn parse_value<T: for<'de> Deserialize<'de>>(flag: &str, value: &str) -> Result<T> {
+    // Enums are read the same way as in the settings file
+    T::deserialize(toml::Value::String(value.to_string()))
+        .map_err(|_| Error::Input(format!("Invalid value for {}: {}", flag, value)))
+}
+
+// The exported images are square, smaller ones can't be made out and larger ones take too long
+const EXPORT_SIZES: std::ops::RangeInclusive<u32> = 16..=4096;
+const MAX_VOLUME: u8 = 100;
+
+impl Settings {
+    // The settings in `path`, or the defaults if there is no such file yet
+    pub fn load(path: &Path) -> Result<Settings> {
+        let invalid = |e: String| Error::Input(format!("{} isn't a valid settings file: {}", path.display(), e));
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                let settings: Settings = toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+                // The file may have been edited by hand
+                if settings.volume > MAX_VOLUME {
+                    return Err(invalid(format!("Invalid volume: {}", settings.volume)));
+                }
+                if !EXPORT_SIZES.contains(&settings.export_size) {
+                    return Err(invalid(format!("Invalid export size: {}", settings.export_size)));
+                }
+                Ok(settings)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self).map_err(|e| Error::Input(e.to_string()))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    // Takes the options that override a setting out of the arguments. They only last until the
+    // program exits unless the settings are saved.
+    pub fn apply_args(&mut self, args: &mut Vec<String>) -> Result<()> {
+        if let Some(host) = take_option(args, "--address") {
+            self.host = host;
+        }
+        if let Some(port) = take_option(args, "--port") {
+            self.port = port.parse().map_err(|_| Error::Input(format!("Invalid port: {}", port)))?;
+        }
+        if let Some(name) = take_option(args, "--name") {
+            self.name = name;
+        }
+        if let Some(theme) = take_option(args, "--theme") {
+            self.theme = parse_value("--theme", &theme)?;
+        }
+        if let Some(pieces) = take_option(args, "--pieces") {
+            self.piece_set = pieces;
+        }
+        if let Some(orientation) = take_option(args, "--orientation") {
+            self.orientation = parse_value("--orientation", &orientation)?;
+        }
+        if let Some(engine) = take_option(args, "--engine") {
+            self.engine_path = Some(PathBuf::from(engine));
+        }
+        if let Some(book) = take_option(args, "--book") {
+            self.book_path = Some(PathBuf::from(book));
+        }
+        if let Some(puzzles) = take_option(args, "--puzzles") {
+            self.puzzle_path = Some(PathBuf::from(puzzles));
+        }
+        if let Some(dir) = take_option(args, "--resources") {
+            self.resource_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(volume) = take_option(args, "--volume") {
+            self.volume = volume
+                .parse()
+                .ok()
+                .filter(|v| *v <= MAX_VOLUME)
+                .ok_or_else(|| Error::Input(format!("Invalid volume: {}", volume)))?;
+        }
+        if let Some(size) = take_option(args, "--export-size") {
+            self.export_size = size
+                .parse()
+                .ok()
+                .filter(|s| EXPORT_SIZES.contains(s))
+                .ok_or_else(|| Error::Input(format!("Invalid export size: {}", size)))?;
+        }
+        if let Some(delay) = take_option(args, "--gif-delay") {
+            self.gif_delay = delay.parse().map_err(|_| Error::Input(format!("Invalid GIF delay: {}", delay)))?;
+        }
+        if let Some(pack) = take_option(args, "--sounds") {
+            self.sound_pack = pack;
+        }
+        if args.iter().any(|a| a == "--mute") {
+            args.retain(|a| a != "--mute");
+            self.sound = false;
+        }
+        if let Some(announcements) = take_option(args, "--announce") {
+            self.announcements = parse_value("--announce", &announcements)?;
+        }
+        if let Some(command) = take_option(args, "--speech-command") {
+            self.speech_command = command;
+        }
+        if args.iter().any(|a| a == "--large-outlines") {
+            args.retain(|a| a != "--large-outlines");
+            self.large_outlines = true;
+        }
+        if args.iter().any(|a| a == "--share-annotations") {
+            args.retain(|a| a != "--share-annotations");
+            self.share_annotations = true;
+        }
+        Ok(())
+    }
+
+    // The address to host on or to connect to when no other is given
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
@@ -0,0 +1,301 @@
+use chess_gui::settings::{Announcements, Orientation, Settings, Theme};
+use ggez::graphics::{self, Canvas, Color, DrawMode, MeshBuilder};
+use ggez::input::keyboard::KeyCode;
+use ggez::Context;
+use glam::Vec2;
+
+use crate::lobby;
+use crate::scene::{Scene, Shared, Transition};
+use crate::utils::*;
+
+const ROW_HEIGHT: f32 = 48.0;
+const LIST_TOP: f32 = 112.0;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Name,
+    Host,
+    Port,
+    Theme,
+    PieceSet,
+    Sound,
+    Volume,
+    SoundPack,
+    Orientation,
+    TimeControl,
+    Engine,
+    Book,
+    Puzzles,
+    ShareAnnotations,
+    Announcements,
+    SpeechCommand,
+    LargeOutlines,
+    ExportSize,
+    GifDelay,
+}
+
+const FIELDS: [Field; 19] = [
+    Field::Name,
+    Field::Host,
+    Field::Port,
+    Field::Theme,
+    Field::PieceSet,
+    Field::Sound,
+    Field::Volume,
+    Field::SoundPack,
+    Field::Orientation,
+    Field::TimeControl,
+    Field::Engine,
+    Field::Book,
+    Field::Puzzles,
+    Field::ShareAnnotations,
+    Field::Announcements,
+    Field::SpeechCommand,
+    Field::LargeOutlines,
+    Field::ExportSize,
+    Field::GifDelay,
+];
+
+// What the player decided when leaving the screen
+pub enum Action {
+    Save,
+    Cancel,
+}
+
+// The next or previous entry of `all` after `current`
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, forward: bool) -> T {
+    let i = all.iter().position(|&t| t == current).unwrap_or(0);
+    let next = if forward { i + 1 } else { i + all.len() - 1 };
+    all[next % all.len()]
+}
+
+// Changes a copy of the settings that is only used once the player saves it
+pub struct SettingsScreen {
+    pub settings: Settings,
+    selected: usize,
+}
+
+impl SettingsScreen {
+    pub fn new(settings: Settings) -> SettingsScreen {
+        SettingsScreen { settings, selected: 0 }
+    }
+
+    fn field(&self) -> Field {
+        FIELDS[self.selected]
+    }
+
+    // Up and down pick a setting, left and right change it, return saves and escape throws the
+    // changes away
+    pub fn key(&mut self, keycode: KeyCode) -> Option<Action> {
+        match keycode {
+            KeyCode::Up => self.selected = (self.selected + FIELDS.len() - 1) % FIELDS.len(),
+            KeyCode::Down => self.selected = (self.selected + 1) % FIELDS.len(),
+            KeyCode::Left => self.change(false),
+            KeyCode::Right => self.change(true),
+            KeyCode::Back => self.erase(),
+            KeyCode::Return => return Some(Action::Save),
+            KeyCode::Escape => return Some(Action::Cancel),
+            _ => (),
+        }
+        None
+    }
+
+    fn change(&mut self, forward: bool) {
+        let field = self.field();
+        let s = &mut self.settings;
+        match field {
+            Field::Theme => s.theme = cycle(&Theme::ALL, s.theme, forward),
+            Field::Sound => s.sound = !s.sound,
+            Field::ShareAnnotations => s.share_annotations = !s.share_annotations,
+            Field::Announcements => s.announcements = cycle(&Announcements::ALL, s.announcements, forward),
+            Field::LargeOutlines => s.large_outlines = !s.large_outlines,
+            Field::ExportSize => s.export_size = if forward { (s.export_size + 80).min(4096) } else { s.export_size.saturating_sub(80).max(80) },
+            Field::GifDelay => s.gif_delay = if forward { s.gif_delay + 100 } else { s.gif_delay.saturating_sub(100).max(100) },
+            Field::Volume => s.volume = if forward { (s.volume + 10).min(100) } else { s.volume.saturating_sub(10) },
+            Field::Orientation => s.orientation = cycle(&Orientation::ALL, s.orientation, forward),
+            Field::TimeControl => {
+                // Going back is going forward through all the others
+                let steps = if forward { 1 } else { lobby::TIME_CONTROLS.len() - 1 };
+                for _ in 0..steps {
+                    s.time_control = lobby::next_time_control(s.time_control);
+                }
+            }
+            Field::Name | Field::Host | Field::Port | Field::PieceSet | Field::SoundPack | Field::Engine | Field::Book | Field::Puzzles | Field::SpeechCommand => (),
+        }
+    }
+
+    fn erase(&mut self) {
+        let field = self.field();
+        let s = &mut self.settings;
+        match field {
+            Field::Name => {
+                s.name.pop();
+            }
+            Field::Host => {
+                s.host.pop();
+            }
+            Field::Port => s.port /= 10,
+            Field::PieceSet => {
+                s.piece_set.pop();
+            }
+            Field::SoundPack => {
+                s.sound_pack.pop();
+            }
+            Field::SpeechCommand => {
+                s.speech_command.pop();
+            }
+            Field::Engine => {
+                let mut path = s.engine_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.pop();
+                s.engine_path = (!path.is_empty()).then(|| path.into());
+            }
+            Field::Book => {
+                let mut path = s.book_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.pop();
+                s.book_path = (!path.is_empty()).then(|| path.into());
+            }
+            Field::Puzzles => {
+                let mut path = s.puzzle_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.pop();
+                s.puzzle_path = (!path.is_empty()).then(|| path.into());
+            }
+            _ => (),
+        }
+    }
+
+    // Typed characters go into the selected text setting
+    pub fn text(&mut self, character: char) {
+        let field = self.field();
+        let s = &mut self.settings;
+        match field {
+            Field::Name => s.name.push(character),
+            Field::Host => s.host.push(character),
+            Field::Port => {
+                if let Some(port) = character
+                    .to_digit(10)
+                    .and_then(|d| s.port.checked_mul(10)?.checked_add(d as u16))
+                {
+                    s.port = port;
+                }
+            }
+            Field::PieceSet => s.piece_set.push(character),
+            Field::SoundPack => s.sound_pack.push(character),
+            Field::SpeechCommand => s.speech_command.push(character),
+            Field::Engine => {
+                let mut path = s.engine_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.push(character);
+                s.engine_path = Some(path.into());
+            }
+            Field::Book => {
+                let mut path = s.book_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.push(character);
+                s.book_path = Some(path.into());
+            }
+            Field::Puzzles => {
+                let mut path = s.puzzle_path.take().map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                path.push(character);
+                s.puzzle_path = Some(path.into());
+            }
+            _ => (),
+        }
+    }
+
+    fn value(&self, field: Field) -> String {
+        let s = &self.settings;
+        match field {
+            Field::Name => s.name.clone(),
+            Field::Host => s.host.clone(),
+            Field::Port => s.port.to_string(),
+            Field::Theme => format!("{:?}", s.theme),
+            Field::PieceSet if s.piece_set.is_empty() => "default".to_string(),
+            Field::PieceSet => s.piece_set.clone(),
+            Field::Sound => if s.sound { "on" } else { "off" }.to_string(),
+            Field::Volume => format!("{}%", s.volume),
+            Field::SoundPack if s.sound_pack.is_empty() => "default".to_string(),
+            Field::SoundPack => s.sound_pack.clone(),
+            Field::Orientation => format!("{:?}", s.orientation),
+            Field::TimeControl => format!("{}+{}", s.time_control.minutes, s.time_control.increment),
+            Field::Engine => s.engine_path.as_ref().map_or("none".to_string(), |p| p.display().to_string()),
+            Field::Book => s.book_path.as_ref().map_or("none".to_string(), |p| p.display().to_string()),
+            Field::Puzzles => s.puzzle_path.as_ref().map_or("none".to_string(), |p| p.display().to_string()),
+            Field::ShareAnnotations => if s.share_annotations { "on" } else { "off" }.to_string(),
+            Field::Announcements => format!("{:?}", s.announcements),
+            Field::SpeechCommand => s.speech_command.clone(),
+            Field::LargeOutlines => if s.large_outlines { "on" } else { "off" }.to_string(),
+            Field::ExportSize => format!("{} pixels", s.export_size),
+            Field::GifDelay => format!("{} ms per move", s.gif_delay),
+        }
+    }
+
+    fn label(field: Field) -> &'static str {
+        match field {
+            Field::Name => "Name",
+            Field::Host => "Address",
+            Field::Port => "Port",
+            Field::Theme => "Board theme",
+            Field::PieceSet => "Piece set",
+            Field::Sound => "Sound",
+            Field::Volume => "Volume",
+            Field::SoundPack => "Sound pack",
+            Field::Orientation => "Board orientation",
+            Field::TimeControl => "Time control",
+            Field::Engine => "Engine",
+            Field::Book => "Opening book",
+            Field::Puzzles => "Puzzles",
+            Field::ShareAnnotations => "Share arrows",
+            Field::Announcements => "Announce moves",
+            Field::SpeechCommand => "Speech command",
+            Field::LargeOutlines => "Large outlines",
+            Field::ExportSize => "Export size",
+            Field::GifDelay => "GIF delay",
+        }
+    }
+
+    fn draw_fields(&self, ctx: &mut Context, canvas: &mut Canvas) {
+        let title = graphics::Text::new("Settings");
+        canvas.draw(&title, graphics::DrawParam::new().dest(Vec2::new(16.0, 16.0)).color(Color::WHITE));
+        let help = graphics::Text::new("Up/Down: pick, Left/Right or type: change, Return: save, Escape: cancel");
+        canvas.draw(&help, graphics::DrawParam::new().dest(Vec2::new(16.0, 64.0)).color(Color::WHITE));
+
+        let mut mb = MeshBuilder::new();
+        mb.rectangle(
+            DrawMode::fill(),
+            graphics::Rect { x: 0.0, y: LIST_TOP + self.selected as f32 * ROW_HEIGHT, w: SCREEN_DIMENSIONS.0 as f32, h: ROW_HEIGHT },
+            Color::from_rgb(40, 60, 80)).expect("Error in building mesh");
+        canvas.draw(&graphics::Mesh::from_data(ctx, mb.build()), graphics::DrawParam::new());
+
+        for (i, &field) in FIELDS.iter().enumerate() {
+            let y = LIST_TOP + i as f32 * ROW_HEIGHT + ROW_HEIGHT / 3.0;
+            let label = graphics::Text::new(SettingsScreen::label(field));
+            canvas.draw(&label, graphics::DrawParam::new().dest(Vec2::new(16.0, y)).color(Color::WHITE));
+            let value = graphics::Text::new(self.value(field));
+            canvas.draw(&value, graphics::DrawParam::new().dest(Vec2::new(320.0, y)).color(Color::WHITE));
+        }
+    }
+}
+
+impl Scene for SettingsScreen {
+    fn draw(&self, ctx: &mut Context, canvas: &mut Canvas, _shared: &Shared) {
+        self.draw_fields(ctx, canvas);
+    }
+
+    fn key_down(&mut self, ctx: &mut Context, shared: &mut Shared, keycode: KeyCode) -> Transition {
+        match self.key(keycode) {
+            Some(Action::Save) => {
+                shared.apply_settings(ctx, self.settings.clone());
+                Transition::Pop
+            }
+            Some(Action::Cancel) => Transition::Pop,
+            None => Transition::Stay,
+        }
+    }
+
+    fn text_input(&mut self, _ctx: &mut Context, _shared: &mut Shared, character: char) -> Transition {
+        self.text(character);
+        Transition::Stay
+    }
+
+    fn opens_settings(&self) -> bool {
+        false
+    }
+}
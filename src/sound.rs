@@ -0,0 +1,55 @@
+use crate::rules;
+use crate::session::PlayedMove;
+use chess::board::Board;
+
+// The sound effects of a sound pack. Each is a file named after it, e.g. capture.wav.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Move,
+    Capture,
+    Castle,
+    Check,
+    Promotion,
+    GameStart,
+    GameEnd,
+}
+
+impl Sound {
+    pub const ALL: [Sound; 7] = [
+        Sound::Move,
+        Sound::Capture,
+        Sound::Castle,
+        Sound::Check,
+        Sound::Promotion,
+        Sound::GameStart,
+        Sound::GameEnd,
+    ];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Sound::Move => "move.wav",
+            Sound::Capture => "capture.wav",
+            Sound::Castle => "castle.wav",
+            Sound::Check => "check.wav",
+            Sound::Promotion => "promotion.wav",
+            Sound::GameStart => "game_start.wav",
+            Sound::GameEnd => "game_end.wav",
+        }
+    }
+
+    // The sound for a move that has just been made on `board`. Only one is played per move so a
+    // check wins over how the move was made.
+    pub fn for_move(played: &PlayedMove, board: &Board) -> Sound {
+        if rules::is_in_check(board, board.turn) {
+            Sound::Check
+        } else if played.promotion.is_some() {
+            Sound::Promotion
+        } else if played.san.starts_with("O-O") {
+            Sound::Castle
+        } else if played.san.contains('x') {
+            Sound::Capture
+        } else {
+            Sound::Move
+        }
+    }
+}
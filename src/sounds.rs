@@ -0,0 +1,58 @@
+use chess_gui::sound::Sound;
+#[cfg(feature = "sound")]
+use ggez::audio::{SoundSource, Source};
+use ggez::Context;
+use log::debug;
+#[cfg(feature = "sound")]
+use log::warn;
+
+// The loaded sound pack. Sounds that can't be loaded are left out and stay silent, nothing else
+// stops working. Built without the sound feature nothing is loaded at all.
+pub struct Sounds {
+    #[cfg(feature = "sound")]
+    sources: Vec<(Sound, Source)>,
+}
+
+#[cfg(not(feature = "sound"))]
+impl Sounds {
+    pub fn new(_ctx: &Context, _pack: &str) -> Sounds {
+        debug!("Built without the sound feature, playing without sound");
+        Sounds {}
+    }
+
+    pub fn play(&mut self, _ctx: &Context, _sound: Sound, _volume: u8) {}
+}
+
+#[cfg(feature = "sound")]
+impl Sounds {
+    // Like the pieces the default sounds are at the top of the sounds directory and every other
+    // pack has a directory in it
+    pub fn new(ctx: &Context, pack: &str) -> Sounds {
+        let dir = if pack.is_empty() { "/sounds".to_string() } else { format!("/sounds/{}", pack) };
+        let mut sources = Vec::new();
+        let mut failures = Vec::new();
+        for sound in Sound::ALL {
+            let path = format!("{}/{}", dir, sound.file_name());
+            match Source::new(ctx, &path) {
+                Ok(source) => sources.push((sound, source)),
+                Err(e) => failures.push(format!("Failed to load {}: {}", path, e)),
+            }
+        }
+        // When nothing loads there most likely is no audio device, once is enough to say so
+        match failures.first() {
+            Some(failure) if sources.is_empty() => warn!("Sound is unavailable. {}", failure),
+            _ => failures.iter().for_each(|f| debug!("{}", f)),
+        }
+        Sounds { sources }
+    }
+
+    // `volume` is in percent
+    pub fn play(&mut self, ctx: &Context, sound: Sound, volume: u8) {
+        if let Some((_, source)) = self.sources.iter_mut().find(|(s, _)| *s == sound) {
+            source.set_volume(volume.min(100) as f32 / 100.0);
+            if let Err(e) = source.play_detached(ctx) {
+                debug!("Failed to play {:?}: {}", sound, e);
+            }
+        }
+    }
+}
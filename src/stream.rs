@@ -0,0 +1,180 @@
+use crate::{tls, websocket};
+use rustls::{ClientConnection, ServerConfig, ServerConnection, StreamOwned};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tungstenite::WebSocket;
+
+// Our own handshakes as a client are done on a blocking socket, a host that stops answering
+// mustn't hang us. Hosts give up on clients that take longer.
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) fn set_blocking(stream: &TcpStream, blocking: bool) -> std::io::Result<()> {
+    stream.set_read_timeout(if blocking { Some(HANDSHAKE_TIMEOUT) } else { None })?;
+    stream.set_write_timeout(if blocking { Some(HANDSHAKE_TIMEOUT) } else { None })?;
+    stream.set_nonblocking(!blocking)
+}
+
+// A byte stream to a peer, either plain TCP or TCP wrapped in TLS
+pub enum Socket {
+    Tcp(TcpStream),
+    TlsClient(Box<StreamOwned<ClientConnection, TcpStream>>),
+    TlsServer(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+impl Socket {
+    pub fn tcp(&self) -> &TcpStream {
+        match self {
+            Socket::Tcp(s) => s,
+            Socket::TlsClient(s) => s.get_ref(),
+            Socket::TlsServer(s) => s.get_ref(),
+        }
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(s) => s.read(buf),
+            Socket::TlsClient(s) => s.read(buf),
+            Socket::TlsServer(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Socket::Tcp(s) => s.write(buf),
+            Socket::TlsClient(s) => s.write(buf),
+            Socket::TlsServer(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Socket::Tcp(s) => s.flush(),
+            Socket::TlsClient(s) => s.flush(),
+            Socket::TlsServer(s) => s.flush(),
+        }
+    }
+}
+
+// Messages aren't framed on a socket, each one is written on its own. Several can still arrive in
+// one read and a long one can be split over reads, `transport::Connection` tells them apart.
+pub(crate) fn write_socket(socket: &mut impl Write, data: &[u8]) -> std::io::Result<()> {
+    socket.write_all(data)?;
+    socket.flush()
+}
+
+// Other implementations read each message into a buffer this large, nothing we send may be longer
+pub const READ_SIZE: usize = 512;
+
+pub(crate) fn read_socket(socket: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut buf = [0_u8; READ_SIZE];
+    match socket.read(&mut buf) {
+        Ok(0) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        Ok(n) => Ok(Some(buf[..n].to_vec())),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// A connection to a peer, see `transport` for how messages are sent over it
+pub enum Stream {
+    Socket(Socket),
+    WebSocket(Box<WebSocket<Socket>>),
+}
+
+impl Stream {
+    pub fn socket(&self) -> &Socket {
+        match self {
+            Stream::Socket(s) => s,
+            Stream::WebSocket(ws) => ws.get_ref(),
+        }
+    }
+
+    pub fn tcp(&self) -> &TcpStream {
+        self.socket().tcp()
+    }
+}
+
+impl From<TcpStream> for Stream {
+    fn from(stream: TcpStream) -> Stream {
+        Stream::Socket(Socket::Tcp(stream))
+    }
+}
+
+impl From<Socket> for Stream {
+    fn from(socket: Socket) -> Stream {
+        Stream::Socket(socket)
+    }
+}
+
+// How far the handshake of a connection someone made to our listener has got
+pub(crate) enum AcceptState {
+    // Waiting for the first bytes to tell a WebSocket from plain TCP
+    Sniffing(TcpStream),
+    Tls(TcpStream, Box<ServerConnection>),
+    WebSocket(Box<websocket::Handshake>),
+    Done(Stream),
+}
+
+impl AcceptState {
+    fn advance(self) -> std::io::Result<AcceptState> {
+        match self {
+            AcceptState::Sniffing(stream) => match websocket::is_websocket(&stream)? {
+                Some(true) => websocket::accept(Socket::Tcp(stream)),
+                Some(false) => Ok(AcceptState::Done(stream.into())),
+                None => Ok(AcceptState::Sniffing(stream)),
+            },
+            AcceptState::Tls(mut stream, mut conn) => {
+                if tls::accept(&mut stream, &mut conn)? {
+                    Ok(AcceptState::Done(Socket::TlsServer(Box::new(StreamOwned::new(*conn, stream))).into()))
+                } else {
+                    Ok(AcceptState::Tls(stream, conn))
+                }
+            }
+            AcceptState::WebSocket(handshake) => websocket::resume(handshake),
+            AcceptState::Done(stream) => Ok(AcceptState::Done(stream)),
+        }
+    }
+}
+
+// A connection someone made to our listener while its handshake is going on. It is polled along
+// with everything else so a peer that is slow or never says anything doesn't hold up the rest.
+pub struct Accepting {
+    state: Option<AcceptState>,
+    deadline: Instant,
+}
+
+impl Accepting {
+    // With `tls` the peer has to do the TLS handshake, otherwise it may use plain TCP or open a
+    // WebSocket
+    pub fn new(stream: TcpStream, tls: Option<&Arc<ServerConfig>>) -> std::io::Result<Accepting> {
+        stream.set_nonblocking(true)?;
+        let state = match tls {
+            Some(config) => AcceptState::Tls(stream, Box::new(ServerConnection::new(config.clone()).map_err(tls::to_io_error)?)),
+            None => AcceptState::Sniffing(stream),
+        };
+        Ok(Accepting { state: Some(state), deadline: Instant::now() + HANDSHAKE_TIMEOUT })
+    }
+
+    // The non blocking stream once the handshake is done. Fails if the handshake does or takes
+    // longer than HANDSHAKE_TIMEOUT.
+    pub fn poll(&mut self) -> std::io::Result<Option<Stream>> {
+        let state = self.state.take().ok_or(std::io::ErrorKind::NotConnected)?;
+        match state.advance()? {
+            AcceptState::Done(stream) => Ok(Some(stream)),
+            // A peer that hasn't said anything yet is taken to use plain TCP, like before the
+            // WebSocket support
+            AcceptState::Sniffing(stream) if Instant::now() >= self.deadline => Ok(Some(stream.into())),
+            _ if Instant::now() >= self.deadline => Err(std::io::ErrorKind::TimedOut.into()),
+            state => {
+                self.state = Some(state);
+                Ok(None)
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use crate::logging::NETWORKING;
+use crate::stream::{set_blocking, Socket, Stream};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::{Certificate, ClientConfig, ClientConnection, PrivateKey, ServerConfig, ServerConnection, ServerName, StreamOwned};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// Certificates aren't checked against a name, every peer uses the same one
+const SERVER_NAME: &str = "chess-gui";
+
+// Where the certificate and the known peers are kept unless told otherwise
+pub fn default_dir() -> PathBuf {
+    dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")).join("chess-gui")
+}
+
+pub(crate) fn to_io_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+// SHA-256 of a certificate as groups of hex digits, short enough for players to read to each other
+pub fn fingerprint(certificate: &Certificate) -> String {
+    let hex: Vec<String> = Sha256::digest(&certificate.0).iter().map(|b| format!("{:02X}", b)).collect();
+    hex.chunks(2).map(|c| c.concat()).collect::<Vec<String>>().join(" ")
+}
+
+// Our self signed certificate
+pub struct Identity {
+    pub certificate: Certificate,
+    key: PrivateKey,
+}
+
+impl Identity {
+    // Loads the certificate from `dir`, on first run a new one is generated and saved there
+    pub fn load_or_generate(dir: &Path) -> std::io::Result<Identity> {
+        let cert_path = dir.join("cert.der");
+        let key_path = dir.join("key.der");
+        if cert_path.exists() && key_path.exists() {
+            return Ok(Identity {
+                certificate: Certificate(std::fs::read(cert_path)?),
+                key: PrivateKey(std::fs::read(key_path)?),
+            });
+        }
+
+        let generated = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).map_err(to_io_error)?;
+        let identity = Identity {
+            certificate: Certificate(generated.serialize_der().map_err(to_io_error)?),
+            key: PrivateKey(generated.serialize_private_key_der()),
+        };
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(cert_path, &identity.certificate.0)?;
+        std::fs::write(key_path, &identity.key.0)?;
+        Ok(identity)
+    }
+
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.certificate)
+    }
+
+    pub fn server_config(&self) -> std::io::Result<Arc<ServerConfig>> {
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(vec![self.certificate.clone()], self.key.clone())
+            .map_err(to_io_error)?;
+        Ok(Arc::new(config))
+    }
+}
+
+// The fingerprints of the hosts we have connected to before, one "address fingerprint" per line
+pub struct KnownPeers {
+    path: PathBuf,
+}
+
+impl KnownPeers {
+    pub fn new(path: PathBuf) -> KnownPeers {
+        KnownPeers { path }
+    }
+
+    fn load(&self) -> HashMap<String, String> {
+        let contents = std::fs::read_to_string(&self.path).unwrap_or_default();
+        contents
+            .lines()
+            .filter_map(|l| l.split_once(' '))
+            .map(|(peer, fingerprint)| (peer.to_string(), fingerprint.to_string()))
+            .collect()
+    }
+
+    fn pin(&self, peer: &str, fingerprint: &str) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{} {}", peer, fingerprint)
+    }
+}
+
+// Trust on first use: the first certificate a host shows is pinned and it has to show the same one
+// every time after that
+struct TofuVerifier {
+    peer: String,
+    known_peers: KnownPeers,
+}
+
+impl ServerCertVerifier for TofuVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let fingerprint = fingerprint(end_entity);
+        match self.known_peers.load().get(&self.peer) {
+            Some(pinned) if *pinned == fingerprint => Ok(ServerCertVerified::assertion()),
+            Some(pinned) => Err(rustls::Error::General(format!(
+                "The certificate of {} has changed from {} to {}. Remove it from {} if you trust the new one",
+                self.peer,
+                pinned,
+                fingerprint,
+                self.known_peers.path.display()
+            ))),
+            None => {
+                log::info!(target: NETWORKING, "Pinning certificate {} for {}", fingerprint, self.peer);
+                self.known_peers
+                    .pin(&self.peer, &fingerprint)
+                    .map_err(|e| rustls::Error::General(e.to_string()))?;
+                Ok(ServerCertVerified::assertion())
+            }
+        }
+    }
+}
+
+// Does the TLS handshake with the host at `address`. The returned stream is non blocking.
+pub fn connect(mut stream: TcpStream, address: &str, known_peers: KnownPeers) -> std::io::Result<Socket> {
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(TofuVerifier { peer: address.to_string(), known_peers }))
+        .with_no_client_auth();
+    let name = ServerName::try_from(SERVER_NAME).map_err(to_io_error)?;
+    let mut conn = ClientConnection::new(Arc::new(config), name).map_err(to_io_error)?;
+
+    set_blocking(&stream, true)?;
+    while conn.is_handshaking() {
+        conn.complete_io(&mut stream)?;
+    }
+    set_blocking(&stream, false)?;
+    Ok(Socket::TlsClient(Box::new(StreamOwned::new(conn, stream))))
+}
+
+// Takes the TLS handshake with a client that connected to us as far as it goes without blocking,
+// returns wether it is done. `stream` has to be non blocking.
+pub(crate) fn accept(stream: &mut TcpStream, conn: &mut ServerConnection) -> std::io::Result<bool> {
+    while conn.is_handshaking() {
+        match conn.complete_io(stream) {
+            Ok(_) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+// The fingerprint of the certificate the host showed us
+pub fn peer_fingerprint(stream: &Stream) -> Option<String> {
+    match stream.socket() {
+        Socket::TlsClient(s) => s.conn.peer_certificates()?.first().map(fingerprint),
+        _ => None,
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::Result;
+use crate::logging::NETWORKING;
+use crate::networking::{C2sMessage, S2cMessage};
+use crate::stream::{self, Stream};
+use crate::{tls, websocket};
+use log::trace;
+use prost::Message;
+use std::marker::PhantomData;
+use std::net::TcpStream;
+use std::sync::mpsc;
+
+// Something whole messages can be sent over and polled for without blocking
+pub trait Transport: Send {
+    fn send_message(&mut self, data: &[u8]) -> std::io::Result<()>;
+    // None if nothing has arrived. A closed connection is reported as an UnexpectedEof error.
+    fn receive_message(&mut self) -> std::io::Result<Option<Vec<u8>>>;
+
+    // The fingerprint of the TLS certificate the host showed us
+    fn fingerprint(&self) -> Option<String> {
+        None
+    }
+}
+
+// On a socket every message is written on its own, on a WebSocket every message is a binary frame
+impl Transport for Stream {
+    fn send_message(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self {
+            Stream::Socket(s) => stream::write_socket(s, data),
+            Stream::WebSocket(ws) => websocket::write_message(ws, data),
+        }
+    }
+
+    fn receive_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        match self {
+            Stream::Socket(s) => stream::read_socket(s),
+            Stream::WebSocket(ws) => websocket::read_message(ws),
+        }
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+        tls::peer_fingerprint(self)
+    }
+}
+
+// Has to be non blocking
+impl Transport for TcpStream {
+    fn send_message(&mut self, data: &[u8]) -> std::io::Result<()> {
+        stream::write_socket(self, data)
+    }
+
+    fn receive_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        stream::read_socket(self)
+    }
+}
+
+// One end of an in memory connection, for tests and games that don't leave the process
+pub struct Channel {
+    sender: mpsc::Sender<Vec<u8>>,
+    receiver: mpsc::Receiver<Vec<u8>>,
+}
+
+// Both ends of a new in memory connection
+pub fn channel() -> (Channel, Channel) {
+    let (a_sender, b_receiver) = mpsc::channel();
+    let (b_sender, a_receiver) = mpsc::channel();
+    (
+        Channel { sender: a_sender, receiver: a_receiver },
+        Channel { sender: b_sender, receiver: b_receiver },
+    )
+}
+
+impl Transport for Channel {
+    fn send_message(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.sender
+            .send(data.to_vec())
+            .map_err(|_| std::io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn receive_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        match self.receiver.try_recv() {
+            Ok(data) => Ok(Some(data)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+// Longer messages are taken for garbage instead of waiting for the rest of them
+const MAX_MESSAGE_LEN: u64 = 64 * 1024;
+
+// Reads a varint from the start of `data`, with the number of bytes it takes up. None if it
+// hasn't arrived whole yet.
+fn varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    // Varints are never longer than 10 bytes, a longer one is taken as too large for anything
+    (data.len() >= 10).then_some((u64::MAX, 10))
+}
+
+// How long the message at the start of `data` is, None if it hasn't arrived whole yet. Sockets
+// don't frame messages, but every message is a single field of its oneof: a key, the length and
+// the contents. Anything else is taken whole so that decoding it reports the error.
+fn message_len(data: &[u8]) -> Option<usize> {
+    // The wire type is in the lowest bits of the key, 2 for a length delimited field
+    if *data.first()? & 0b111 != 2 {
+        return Some(data.len());
+    }
+    // Field numbers and the wire type fit into 32 bits
+    let (key, key_len) = varint(data)?;
+    if key > u32::MAX as u64 {
+        return Some(data.len());
+    }
+    let (len, len_len) = varint(&data[key_len..])?;
+    if len > MAX_MESSAGE_LEN {
+        return Some(data.len());
+    }
+    let total = key_len + len_len + len as usize;
+    (total <= data.len()).then_some(total)
+}
+
+// A transport that sends `Out` messages and receives `In` messages
+pub struct Connection<Out, In> {
+    transport: Box<dyn Transport>,
+    // What has been received but isn't a whole message yet, or came in the same read as the
+    // message before it
+    buffer: Vec<u8>,
+    messages: PhantomData<fn(Out) -> In>,
+}
+
+// Our end of a connection to the host
+pub type ClientConnection = Connection<C2sMessage, S2cMessage>;
+// The host's end of a connection to a player or spectator
+pub type HostConnection = Connection<S2cMessage, C2sMessage>;
+
+impl<Out: Message, In: Message + Default> Connection<Out, In> {
+    pub fn new(transport: impl Transport + 'static) -> Connection<Out, In> {
+        Connection {
+            transport: Box::new(transport),
+            buffer: Vec::new(),
+            messages: PhantomData,
+        }
+    }
+
+    pub fn send(&mut self, message: &Out) -> Result<()> {
+        Ok(self.transport.send_message(&message.encode_to_vec())?)
+    }
+
+    // Receives a message if one is available. A message that can't be decoded is reported as a
+    // decode error.
+    pub fn poll(&mut self) -> Result<Option<In>> {
+        loop {
+            if let Some(len) = message_len(&self.buffer) {
+                let data: Vec<u8> = self.buffer.drain(..len).collect();
+                trace!(target: NETWORKING, "Received {} bytes", data.len());
+                return Ok(Some(In::decode(data.as_slice())?));
+            }
+            match self.transport.receive_message()? {
+                Some(data) => self.buffer.extend(data),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    pub fn fingerprint(&self) -> Option<String> {
+        self.transport.fingerprint()
+    }
+}
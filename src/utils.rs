@@ -6,6 +6,21 @@ const GRID_SIZE: i16 = 8;
 pub const CELL_DIMENSIONS: (i16, i16) = (CELL_SIZE, CELL_SIZE);
 pub const GRID_DIMENSIONS: (i16, i16) = (GRID_SIZE, GRID_SIZE);
 pub const SCREEN_DIMENSIONS: (i16, i16) = (CELL_DIMENSIONS.0 * GRID_DIMENSIONS.0, CELL_DIMENSIONS.1 * GRID_DIMENSIONS.1);
+// The side panel is drawn to the right of the board
+pub const PANEL_WIDTH: i16 = 480;
+// The names of the players and the opening are shown at the top of the side panel, above the chat
+pub const PLAYERS_HEIGHT: i16 = 100;
+pub const WINDOW_DIMENSIONS: (i16, i16) = (SCREEN_DIMENSIONS.0 + PANEL_WIDTH, SCREEN_DIMENSIONS.1);
+
+// Clicks are turned into squares the same way the board is drawn
+pub use chess_gui::render::flip;
+
+// The square drawn under a point in the window, None outside of the board
+pub fn square_at(x: f32, y: f32) -> Option<chess::util::Pos> {
+    let (column, row) = ((x / CELL_DIMENSIONS.0 as f32).floor(), (y / CELL_DIMENSIONS.1 as f32).floor());
+    let on_board = |i: f32| (0.0..GRID_SIZE as f32).contains(&i);
+    (on_board(column) && on_board(row)).then_some(chess::util::Pos { x: column as i8, y: row as i8 })
+}
 pub struct Imglib {
     pub black_pawn: Image,
     pub black_rook: Image, 
@@ -21,21 +36,24 @@ pub struct Imglib {
     pub white_king: Image
 }
 impl Imglib {
-    pub fn new(ctx: &mut Context) -> GameResult<Imglib> {
+    // The default pieces are at the top of the resources, every other set has a directory
+    pub fn new(ctx: &mut Context, piece_set: &str) -> GameResult<Imglib> {
+        let dir = if piece_set.is_empty() { String::new() } else { format!("/{}", piece_set) };
+        let load = |name: &str| Image::from_path(&*ctx, format!("{}/{}.png", dir, name), true);
         Ok(
         Imglib { 
-            black_pawn: Image::from_path(ctx, "/b_pawn.png", true)?, 
-            black_rook: Image::from_path(ctx, "/b_rook.png", true)?,
-            black_knight: Image::from_path(ctx, "/b_knight.png", true)?,
-            black_bishop: Image::from_path(ctx, "/b_bishop.png", true)?, 
-            black_queen: Image::from_path(ctx, "/b_queen.png", true)?, 
-            black_king: Image::from_path(ctx, "/b_king.png", true)?, 
-            white_pawn: Image::from_path(ctx, "/w_pawn.png", true)?, 
-            white_rook: Image::from_path(ctx, "/w_rook.png", true)?, 
-            white_knight: Image::from_path(ctx, "/w_knight.png", true)?,
-            white_bishop: Image::from_path(ctx, "/w_bishop.png", true)?, 
-            white_queen: Image::from_path(ctx, "/w_queen.png", true)?, 
-            white_king: Image::from_path(ctx, "/w_king.png", true)?
+            black_pawn: load("b_pawn")?, 
+            black_rook: load("b_rook")?,
+            black_knight: load("b_knight")?,
+            black_bishop: load("b_bishop")?, 
+            black_queen: load("b_queen")?, 
+            black_king: load("b_king")?, 
+            white_pawn: load("w_pawn")?, 
+            white_rook: load("w_rook")?, 
+            white_knight: load("w_knight")?,
+            white_bishop: load("w_bishop")?, 
+            white_queen: load("w_queen")?, 
+            white_king: load("w_king")?
         })
     }
 }
@@ -0,0 +1,85 @@
+use crate::stream::{set_blocking, AcceptState, Socket, Stream};
+use std::net::TcpStream;
+use tungstenite::handshake::server::{NoCallback, ServerHandshake};
+use tungstenite::handshake::{HandshakeError, MidHandshake};
+use tungstenite::{Message, WebSocket};
+
+fn to_io_error(e: tungstenite::Error) -> std::io::Error {
+    match e {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => std::io::ErrorKind::UnexpectedEof.into(),
+        e => std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()),
+    }
+}
+
+// Wether the peer opened the connection with a WebSocket handshake, None until enough of it has
+// arrived to tell. Our own clients always send a message first, which never starts like a HTTP
+// request.
+pub(crate) fn is_websocket(stream: &TcpStream) -> std::io::Result<Option<bool>> {
+    const START: &[u8] = b"GET ";
+    let mut buf = [0_u8; 4];
+    match stream.peek(&mut buf) {
+        // The connection was closed, the caller finds out when reading
+        Ok(0) => Ok(Some(false)),
+        Ok(n) if !START.starts_with(&buf[..n]) => Ok(Some(false)),
+        Ok(n) if n == START.len() => Ok(Some(true)),
+        // Only part of the request line has arrived
+        Ok(_) => Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// A WebSocket handshake that is waiting for the peer
+pub(crate) type Handshake = MidHandshake<ServerHandshake<Socket, NoCallback>>;
+
+// Takes the handshake as far as it goes without blocking
+fn progress(result: Result<WebSocket<Socket>, HandshakeError<ServerHandshake<Socket, NoCallback>>>) -> std::io::Result<AcceptState> {
+    match result {
+        Ok(ws) => Ok(AcceptState::Done(Stream::WebSocket(Box::new(ws)))),
+        Err(HandshakeError::Interrupted(handshake)) => Ok(AcceptState::WebSocket(Box::new(handshake))),
+        Err(HandshakeError::Failure(e)) => Err(std::io::Error::other(e.to_string())),
+    }
+}
+
+// Answers the WebSocket handshake of a peer that connected to us. `socket` has to be non blocking.
+pub(crate) fn accept(socket: Socket) -> std::io::Result<AcceptState> {
+    progress(tungstenite::accept(socket))
+}
+
+pub(crate) fn resume(handshake: Box<Handshake>) -> std::io::Result<AcceptState> {
+    progress((*handshake).handshake())
+}
+
+// Opens a WebSocket to `url` over a socket that is already connected to the host, e.g.
+// "ws://example.com:1337/". The returned stream is non blocking.
+pub fn connect(socket: Socket, url: &str) -> std::io::Result<Stream> {
+    set_blocking(socket.tcp(), true)?;
+    let (ws, _) = tungstenite::client(url, socket).map_err(|e| std::io::Error::other(e.to_string()))?;
+    set_blocking(ws.get_ref().tcp(), false)?;
+    Ok(Stream::WebSocket(Box::new(ws)))
+}
+
+pub(crate) fn write_message(ws: &mut WebSocket<Socket>, data: &[u8]) -> std::io::Result<()> {
+    match ws.send(Message::Binary(data.to_vec())) {
+        // The frame is queued and goes out with the next read or write
+        Err(tungstenite::Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => Ok(()),
+        r => r.map_err(to_io_error),
+    }
+}
+
+pub(crate) fn read_message(ws: &mut WebSocket<Socket>) -> std::io::Result<Option<Vec<u8>>> {
+    loop {
+        match ws.read() {
+            Ok(Message::Binary(data)) => return Ok(Some(data)),
+            Ok(Message::Text(_)) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Messages have to be sent as binary WebSocket frames"))
+            }
+            Ok(Message::Close(_)) => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            // Pings are answered by tungstenite
+            Ok(_) => (),
+            Err(tungstenite::Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(to_io_error(e)),
+        }
+    }
+}
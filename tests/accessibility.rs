@@ -0,0 +1,28 @@
+// Moves and positions described in words for players who can't see the board
+mod common;
+use chess::board::Board;
+use chess_gui::accessibility::{describe_move, describe_position};
+use chess_gui::notation::board_from_fen;
+
+// The description of the last of `moves`, given in UCI notation, played from the start
+fn description_after(moves: &[&str]) -> String {
+    common::after(moves, describe_move)
+}
+
+#[test]
+fn moves_are_described() {
+    assert_eq!(description_after(&["g1f3"]), "White knight g1 to f3");
+    assert_eq!(description_after(&["e2e4", "d7d5", "e4d5"]), "White pawn e4 takes d5");
+    assert_eq!(description_after(&["e2e4", "f7f6", "d1h5"]), "White queen d1 to h5, check");
+    assert_eq!(
+        description_after(&["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"]),
+        "White castles kingside"
+    );
+}
+
+#[test]
+fn positions_are_described() {
+    let board = board_from_fen("4k3/8/8/8/8/8/8/R3K2R b - - 0 1").unwrap();
+    assert_eq!(describe_position(&board), "White: king on e1, rooks on a1 and h1. Black: king on e8. Black to move.");
+    assert!(describe_position(&Board::new()).starts_with("White: king on e1, queen on d1, rooks on a1 and h1, "));
+}
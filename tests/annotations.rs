@@ -0,0 +1,41 @@
+// Drawing arrows and circles and sending them over the network
+use chess::util::Pos;
+use chess_gui::annotations::{self, Annotation};
+use chess_gui::networking::{self, AnnotationColor};
+
+fn arrow(from: Pos, to: Pos, color: AnnotationColor) -> Annotation {
+    Annotation { from, to, color }
+}
+
+#[test]
+fn drawing_the_same_annotation_again_removes_it() {
+    let (e2, e4) = (Pos { x: 4, y: 6 }, Pos { x: 4, y: 4 });
+    let mut drawn = Vec::new();
+    annotations::toggle(&mut drawn, arrow(e2, e4, AnnotationColor::Green));
+    annotations::toggle(&mut drawn, arrow(e4, e4, AnnotationColor::Green));
+    assert_eq!(drawn.len(), 2);
+    assert!(drawn[1].is_circle());
+
+    annotations::toggle(&mut drawn, arrow(e2, e4, AnnotationColor::Red));
+    assert_eq!(drawn, vec![arrow(e2, e4, AnnotationColor::Red), arrow(e4, e4, AnnotationColor::Green)]);
+
+    annotations::toggle(&mut drawn, arrow(e2, e4, AnnotationColor::Red));
+    assert_eq!(drawn, vec![arrow(e4, e4, AnnotationColor::Green)]);
+}
+
+#[test]
+fn modifier_keys_pick_the_color() {
+    assert_eq!(annotations::color_for(false, false), AnnotationColor::Green);
+    assert_eq!(annotations::color_for(true, false), AnnotationColor::Red);
+    assert_eq!(annotations::color_for(false, true), AnnotationColor::Blue);
+    assert_eq!(annotations::color_for(true, true), AnnotationColor::Yellow);
+}
+
+#[test]
+fn annotations_survive_the_protocol() {
+    let a = arrow(Pos { x: 0, y: 7 }, Pos { x: 7, y: 0 }, AnnotationColor::Blue);
+    assert_eq!(Annotation::from_proto(&a.to_proto()), Some(a));
+
+    let off_board = networking::Annotation { from_square: 64, to_square: 0, color: 0 };
+    assert_eq!(Annotation::from_proto(&off_board), None);
+}
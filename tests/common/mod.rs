@@ -0,0 +1,69 @@
+// Helpers shared by the integration tests. Every test file builds its own copy of this module and
+// most of them only use part of it.
+#![allow(dead_code)]
+
+use chess::board::Board;
+use chess::util::Pos;
+use chess_gui::notation::*;
+use chess_gui::session::{Event, PlayedMove, Session};
+use std::time::{Duration, Instant};
+
+// How long anything over the network may take before the test fails
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn square(name: &str) -> Pos {
+    parse_square(name).unwrap()
+}
+
+// A square as the class protocol numbers them, from 0 for a8 to 63 for h1. Worked out by hand so
+// the protocol tests don't rely on the notation they check.
+pub fn square_index(name: &str) -> u32 {
+    let b = name.as_bytes();
+    let file = (b[0] - b'a') as u32;
+    let rank = (b[1] - b'1') as u32;
+    file + (7 - rank) * 8
+}
+
+// Calls `step` until it returns true, the test fails if that takes longer than `TIMEOUT`
+pub fn wait_until(waiting_for: &str, mut step: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !step() {
+        assert!(start.elapsed() < TIMEOUT, "Timed out waiting for {}", waiting_for);
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// Polls the session until it reports `expected`
+pub fn poll_until(session: &mut Session, expected: Event) {
+    wait_until(&format!("{:?}", expected), || session.poll().contains(&expected));
+}
+
+// Polls both sessions until `receiver` reports `expected`
+pub fn poll_both_until(sender: &mut Session, receiver: &mut Session, expected: Event) {
+    wait_until(&format!("{:?}", expected), || {
+        sender.poll();
+        receiver.poll().contains(&expected)
+    });
+}
+
+// Polls until the session reports an event `pick` accepts and returns what it made of it
+pub fn poll_for<T>(session: &mut Session, mut pick: impl FnMut(&Event) -> Option<T>) -> T {
+    let mut found = None;
+    wait_until("an event", || {
+        found = session.poll().iter().find_map(&mut pick);
+        found.is_some()
+    });
+    found.unwrap()
+}
+
+// Plays `moves`, given in UCI notation, from the start and hands the last of them and the board
+// after it to `look`
+pub fn after<T>(moves: &[&str], look: impl FnOnce(&PlayedMove, &Board) -> T) -> T {
+    let mut session = Session::new();
+    for m in moves {
+        let (from, to, promotion) = uci_to_move(m).unwrap();
+        session.make_move(from, to, promotion);
+    }
+    assert_eq!(session.history.len(), moves.len(), "{:?} isn't a legal game", moves);
+    look(session.history.last().unwrap(), &session.board)
+}
@@ -0,0 +1,84 @@
+// Players joining a hosted game over loopback with names, tokens and game passwords
+mod common;
+use chess_gui::networking::PlayerIdentity;
+use chess_gui::notation::*;
+use chess_gui::session::{Event, Session};
+use common::wait_until;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+fn player(name: &str, token: u8, password: Option<&str>) -> PlayerIdentity {
+    PlayerIdentity {
+        name: name.to_string(),
+        token: vec![token; 32],
+        password: password.map(|p| p.to_string()),
+    }
+}
+
+fn host(password: Option<&str>) -> (Session, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    (Session::host(listener, player("alice", 1, password)), addr)
+}
+
+// Connects a client and returns it with the events up to the host's answer
+fn join(host: &mut Session, addr: SocketAddr, identity: PlayerIdentity) -> (Session, Vec<Event>) {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nonblocking(true).unwrap();
+    let mut client = Session::client(stream, false, identity);
+    let mut events = Vec::new();
+    wait_until("the host to answer", || {
+        host.poll();
+        events.extend(client.poll());
+        events.iter().any(|e| matches!(e, Event::Connected { .. }))
+    });
+    (client, events)
+}
+
+fn refused_with(events: &[Event], error: &str) -> bool {
+    events.contains(&Event::Connected { success: false }) && events.contains(&Event::Error(error.to_string()))
+}
+
+#[test]
+fn names_are_exchanged() {
+    let (mut host, addr) = host(None);
+    let (client, events) = join(&mut host, addr, player("bob", 2, None));
+    assert!(events.contains(&Event::Connected { success: true }));
+    assert_eq!(client.white_name.as_deref(), Some("alice"));
+    assert_eq!(client.black_name.as_deref(), Some("bob"));
+    assert_eq!(host.black_name.as_deref(), Some("bob"));
+}
+
+#[test]
+fn wrong_password_is_refused() {
+    let (mut host, addr) = host(Some("secret"));
+    let (_, events) = join(&mut host, addr, player("bob", 2, None));
+    assert!(refused_with(&events, "Wrong game password"));
+    let (_, events) = join(&mut host, addr, player("bob", 2, Some("guess")));
+    assert!(refused_with(&events, "Wrong game password"));
+    let (_, events) = join(&mut host, addr, player("bob", 2, Some("secret")));
+    assert!(events.contains(&Event::Connected { success: true }));
+}
+
+#[test]
+fn only_the_same_player_can_reconnect() {
+    let (mut host, addr) = host(None);
+    let (client, _) = join(&mut host, addr, player("bob", 2, None));
+    let (_, events) = join(&mut host, addr, player("carol", 3, None));
+    assert!(refused_with(&events, "The game already has two players"));
+
+    host.make_move(square_to_pos(52), square_to_pos(36), None);
+    drop(client);
+    wait_until("the host to notice the disconnect", || {
+        host.poll();
+        !host.is_connected()
+    });
+
+    // Someone else using the same name isn't enough
+    let (_, events) = join(&mut host, addr, player("bob", 3, None));
+    assert!(refused_with(&events, "Only bob can take the empty seat"));
+
+    let (client, events) = join(&mut host, addr, player("bob", 2, None));
+    assert!(events.contains(&Event::Connected { success: true }));
+    assert_eq!(board_to_fen(&client.board), board_to_fen(&host.board));
+}
@@ -0,0 +1,326 @@
+// Plays scripted games against `Session` over loopback the way other implementations of the class
+// protocol would: one message per write and squares as indices 0-63.
+mod common;
+use chess_gui::networking::{self, c2s_message, s2c_message, C2sMessage, S2cMessage};
+use chess_gui::notation::*;
+use chess_gui::session::{Event, Session};
+use common::{poll_until, square_index, wait_until, TIMEOUT};
+use prost::Message;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+// The other end of the connection, driven step by step by the test
+struct ScriptedPeer {
+    stream: TcpStream,
+}
+
+impl ScriptedPeer {
+    fn new(stream: TcpStream) -> ScriptedPeer {
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        ScriptedPeer { stream }
+    }
+
+    fn send(&mut self, data: &impl Message) {
+        self.stream.write_all(&data.encode_to_vec()).unwrap();
+    }
+
+    fn receive<M: Message + Default>(&mut self) -> M {
+        let mut buf = [0_u8; 512];
+        let n = self.stream.read(&mut buf).expect("Peer didn't get a message");
+        M::decode(&buf[..n]).expect("Peer couldn't decode the message")
+    }
+}
+
+// Moves in coordinate notation with an optional promotion piece, e.g. "h7g8q"
+fn parse_move(m: &str) -> networking::Move {
+    let promotion = match m.as_bytes().get(4) {
+        Some(b'q') => Some(networking::Piece::Queen as i32),
+        Some(b'r') => Some(networking::Piece::Rook as i32),
+        Some(b'b') => Some(networking::Piece::Bishop as i32),
+        Some(b'n') => Some(networking::Piece::Knight as i32),
+        _ => None,
+    };
+    networking::Move {
+        from_square: square_index(&m[0..2]),
+        to_square: square_index(&m[2..4]),
+        promotion,
+    }
+}
+
+fn placement(session: &Session) -> String {
+    board_to_fen(&session.board).split(' ').next().unwrap().to_string()
+}
+
+// Makes one of our own moves and checks that the peer receives exactly that move
+fn our_move<M: Message + Default>(session: &mut Session, peer: &mut ScriptedPeer, m: &networking::Move, unwrap: fn(M) -> Option<networking::Move>) {
+    session.make_move(
+        square_to_pos(m.from_square),
+        square_to_pos(m.to_square),
+        promotion_from_proto(m.promotion),
+    );
+    assert_eq!(unwrap(peer.receive()), Some(m.clone()));
+}
+
+fn s2c_move(data: S2cMessage) -> Option<networking::Move> {
+    match data.msg {
+        Some(s2c_message::Msg::Move(m)) => Some(m),
+        _ => None,
+    }
+}
+
+fn c2s_move(data: C2sMessage) -> Option<networking::Move> {
+    match data.msg {
+        Some(c2s_message::Msg::Move(m)) => Some(m),
+        _ => None,
+    }
+}
+
+// Hosts a game with `Session` playing white against a scripted client
+fn start_host() -> (Session, ScriptedPeer, std::net::SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let mut session = Session::host(listener, Default::default());
+
+    let mut peer = ScriptedPeer::new(TcpStream::connect(addr).unwrap());
+    peer.send(&C2sMessage {
+        msg: Some(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+            game_id: 0,
+            spectate: false,
+            // Peers from before versioning don't send any handshake fields
+            ..Default::default()
+        })),
+    });
+    wait_until("the host to accept the client", || {
+        session.poll();
+        session.is_connected()
+    });
+
+    match peer.receive::<S2cMessage>().msg {
+        Some(s2c_message::Msg::ConnectAck(ack)) => {
+            assert!(ack.success);
+            assert_eq!(ack.client_is_white, Some(false));
+        }
+        other => panic!("Expected a connect ack, got {:?}", other),
+    }
+    (session, peer, addr)
+}
+
+// Joins a scripted host with `Session` playing black
+fn start_client() -> (Session, ScriptedPeer) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    stream.set_nonblocking(true).unwrap();
+    let mut session = Session::client(stream, false, Default::default());
+
+    let mut peer = ScriptedPeer::new(listener.accept().unwrap().0);
+    match peer.receive::<C2sMessage>().msg {
+        Some(c2s_message::Msg::ConnectRequest(r)) => assert!(!r.spectate),
+        other => panic!("Expected a connect request, got {:?}", other),
+    }
+    peer.send(&S2cMessage {
+        msg: Some(s2c_message::Msg::ConnectAck(networking::S2cConnectAck {
+            success: true,
+            game_id: Some(0),
+            starting_position: Some(networking::BoardState {
+                fen_string: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+            }),
+            client_is_white: Some(false),
+            ..Default::default()
+        })),
+    });
+    poll_until(&mut session, Event::Connected { success: true });
+    (session, peer)
+}
+
+fn play_as_host(moves: &[&str], expected: &str) {
+    let (mut session, mut peer, _) = start_host();
+    for (i, m) in moves.iter().map(|m| parse_move(m)).enumerate() {
+        if i % 2 == 0 {
+            our_move(&mut session, &mut peer, &m, s2c_move);
+        } else {
+            peer.send(&C2sMessage { msg: Some(c2s_message::Msg::Move(m)) });
+            poll_until(&mut session, Event::Moved);
+        }
+    }
+    assert_eq!(placement(&session), expected);
+}
+
+fn play_as_client(moves: &[&str], expected: &str) {
+    let (mut session, mut peer) = start_client();
+    for (i, m) in moves.iter().map(|m| parse_move(m)).enumerate() {
+        if i % 2 == 0 {
+            peer.send(&S2cMessage { msg: Some(s2c_message::Msg::Move(m)) });
+            poll_until(&mut session, Event::Moved);
+        } else {
+            our_move(&mut session, &mut peer, &m, c2s_move);
+        }
+    }
+    assert_eq!(placement(&session), expected);
+}
+
+const CASTLING: [&str; 8] = ["e2e4", "e7e5", "g1f3", "g8f6", "f1c4", "f8c5", "e1g1", "e8g8"];
+const CASTLING_RESULT: &str = "rnbq1rk1/pppp1ppp/5n2/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1";
+
+const WHITE_EN_PASSANT: [&str; 5] = ["e2e4", "a7a6", "e4e5", "d7d5", "e5d6"];
+const WHITE_EN_PASSANT_RESULT: &str = "rnbqkbnr/1pp1pppp/p2P4/8/8/8/PPPP1PPP/RNBQKBNR";
+
+const BLACK_EN_PASSANT: [&str; 6] = ["a2a3", "e7e5", "a3a4", "e5e4", "d2d4", "e4d3"];
+const BLACK_EN_PASSANT_RESULT: &str = "rnbqkbnr/pppp1ppp/8/8/P7/3p4/1PP1PPPP/RNBQKBNR";
+
+const WHITE_PROMOTION: [&str; 9] = ["h2h4", "g7g5", "h4g5", "h7h6", "g5h6", "g8f6", "h6h7", "h8g8", "h7g8q"];
+const WHITE_PROMOTION_RESULT: &str = "rnbqkbQ1/pppppp2/5n2/8/8/8/PPPPPPP1/RNBQKBNR";
+
+const BLACK_PROMOTION: [&str; 10] = ["a2a3", "h7h5", "g2g4", "h5g4", "g1f3", "g4g3", "a3a4", "g3h2", "h1g1", "h2g1q"];
+const BLACK_PROMOTION_RESULT: &str = "rnbqkbnr/ppppppp1/8/8/P7/5N2/1PPPPP2/RNBQKBq1";
+
+#[test]
+fn host_castling() {
+    play_as_host(&CASTLING, CASTLING_RESULT);
+}
+
+#[test]
+fn client_castling() {
+    play_as_client(&CASTLING, CASTLING_RESULT);
+}
+
+#[test]
+fn host_en_passant() {
+    play_as_host(&WHITE_EN_PASSANT, WHITE_EN_PASSANT_RESULT);
+    play_as_host(&BLACK_EN_PASSANT, BLACK_EN_PASSANT_RESULT);
+}
+
+#[test]
+fn client_en_passant() {
+    play_as_client(&WHITE_EN_PASSANT, WHITE_EN_PASSANT_RESULT);
+    play_as_client(&BLACK_EN_PASSANT, BLACK_EN_PASSANT_RESULT);
+}
+
+#[test]
+fn host_promotion() {
+    play_as_host(&WHITE_PROMOTION, WHITE_PROMOTION_RESULT);
+    play_as_host(&BLACK_PROMOTION, BLACK_PROMOTION_RESULT);
+}
+
+#[test]
+fn client_promotion() {
+    play_as_client(&WHITE_PROMOTION, WHITE_PROMOTION_RESULT);
+    play_as_client(&BLACK_PROMOTION, BLACK_PROMOTION_RESULT);
+}
+
+#[test]
+fn spectator_gets_position_and_moves() {
+    let (mut session, mut player, addr) = start_host();
+    our_move(&mut session, &mut player, &parse_move("e2e4"), s2c_move);
+
+    let mut spectator = ScriptedPeer::new(TcpStream::connect(addr).unwrap());
+    spectator.send(&C2sMessage {
+        msg: Some(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+            game_id: 0,
+            spectate: true,
+            ..Default::default()
+        })),
+    });
+    wait_until("the host to accept the spectator", || {
+        session.poll();
+        session.spectator_count() > 0
+    });
+    match spectator.receive::<S2cMessage>().msg {
+        Some(s2c_message::Msg::ConnectAck(ack)) => {
+            assert!(ack.success);
+            assert_eq!(ack.client_is_white, None);
+            assert_eq!(ack.starting_position.unwrap().fen_string, board_to_fen(&session.board));
+        }
+        other => panic!("Expected a connect ack, got {:?}", other),
+    }
+
+    // Moves from both players are passed on
+    let reply = parse_move("e7e5");
+    player.send(&C2sMessage { msg: Some(c2s_message::Msg::Move(reply.clone())) });
+    poll_until(&mut session, Event::Moved);
+    assert_eq!(s2c_move(spectator.receive()), Some(reply));
+
+    let next = parse_move("g1f3");
+    our_move(&mut session, &mut player, &next, s2c_move);
+    assert_eq!(s2c_move(spectator.receive()), Some(next));
+}
+
+// Polls the session until it has written something to the peer
+fn poll_until_sent(session: &mut Session, peer: &mut ScriptedPeer) {
+    peer.stream.set_read_timeout(Some(Duration::from_millis(1))).unwrap();
+    wait_until("the session to answer", || {
+        session.poll();
+        peer.stream.peek(&mut [0]).is_ok()
+    });
+    peer.stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+}
+
+#[test]
+fn illegal_moves_are_refused_and_not_passed_on() {
+    let (mut session, mut player, addr) = start_host();
+    let mut spectator = ScriptedPeer::new(TcpStream::connect(addr).unwrap());
+    spectator.send(&C2sMessage {
+        msg: Some(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+            game_id: 0,
+            spectate: true,
+            ..Default::default()
+        })),
+    });
+    poll_until_sent(&mut session, &mut spectator);
+    assert!(matches!(spectator.receive::<S2cMessage>().msg, Some(s2c_message::Msg::ConnectAck(_))));
+    our_move(&mut session, &mut player, &parse_move("e2e4"), s2c_move);
+    assert_eq!(s2c_move(spectator.receive()), Some(parse_move("e2e4")));
+
+    // A pawn can't move three squares
+    assert_refused(&mut session, &mut player, parse_move("e7e4"));
+
+    // The next move the spectator sees is the legal one that follows
+    let reply = parse_move("e7e5");
+    player.send(&C2sMessage { msg: Some(c2s_message::Msg::Move(reply.clone())) });
+    poll_until(&mut session, Event::Moved);
+    assert_eq!(s2c_move(spectator.receive()), Some(reply));
+}
+
+// Sends `m` from the scripted client and checks that the host refuses it and keeps its board
+fn assert_refused(session: &mut Session, player: &mut ScriptedPeer, m: networking::Move) {
+    let before = board_to_fen(&session.board);
+    player.send(&C2sMessage { msg: Some(c2s_message::Msg::Move(m)) });
+    poll_until_sent(session, player);
+    match player.receive::<S2cMessage>().msg {
+        Some(s2c_message::Msg::MoveAck(ack)) => {
+            assert!(!ack.legal);
+            assert_eq!(ack.board_result.unwrap().fen_string, before);
+        }
+        other => panic!("Expected a move ack, got {:?}", other),
+    }
+    assert_eq!(board_to_fen(&session.board), before);
+}
+
+#[test]
+fn client_cant_move_the_hosts_pieces() {
+    let (mut session, mut player, _) = start_host();
+    // Neither before the host has moved nor on its own turn
+    assert_refused(&mut session, &mut player, parse_move("e2e4"));
+    our_move(&mut session, &mut player, &parse_move("e2e4"), s2c_move);
+    assert_refused(&mut session, &mut player, parse_move("d2d4"));
+    assert_refused(&mut session, &mut player, networking::Move { from_square: 64, to_square: 0, promotion: None });
+
+    let reply = parse_move("e7e5");
+    player.send(&C2sMessage { msg: Some(c2s_message::Msg::Move(reply)) });
+    poll_until(&mut session, Event::Moved);
+    assert_eq!(placement(&session), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR");
+}
+
+#[test]
+fn client_ignores_moves_off_the_board() {
+    let (mut session, mut peer) = start_client();
+    peer.send(&S2cMessage {
+        msg: Some(s2c_message::Msg::Move(networking::Move { from_square: 52, to_square: 100, promotion: None })),
+    });
+    // Only the move that follows is reported
+    peer.send(&S2cMessage { msg: Some(s2c_message::Msg::Move(parse_move("e2e4"))) });
+    poll_until(&mut session, Event::Moved);
+    assert_eq!(session.history.len(), 1);
+    assert_eq!(placement(&session), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR");
+}
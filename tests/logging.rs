@@ -0,0 +1,45 @@
+// Parsing of the log filters given with --log or CHESS_LOG
+use chess_gui::logging::{self, Filter, INPUT, NETWORKING, RENDERING};
+use log::LevelFilter;
+
+#[test]
+fn default_level_applies_to_every_target() {
+    let filter = Filter::parse("warn").unwrap();
+    assert_eq!(filter.level_for(NETWORKING), LevelFilter::Warn);
+    assert_eq!(filter.level_for("chess_gui::session"), LevelFilter::Warn);
+}
+
+#[test]
+fn targets_override_the_default() {
+    let filter = Filter::parse("info, networking=trace,input=off").unwrap();
+    assert_eq!(filter.level_for(NETWORKING), LevelFilter::Trace);
+    assert_eq!(filter.level_for(INPUT), LevelFilter::Off);
+    assert_eq!(filter.level_for(RENDERING), LevelFilter::Info);
+}
+
+#[test]
+fn most_specific_module_wins() {
+    let filter = Filter::parse("error,chess_gui=debug,chess_gui::tls=off").unwrap();
+    assert_eq!(filter.level_for("chess_gui::session"), LevelFilter::Debug);
+    assert_eq!(filter.level_for("chess_gui::tls"), LevelFilter::Off);
+    // Only whole module names match
+    assert_eq!(filter.level_for("chess_gui_server"), LevelFilter::Error);
+}
+
+#[test]
+fn unknown_levels_are_refused() {
+    assert!(Filter::parse("loud").is_err());
+    assert!(Filter::parse("networking=everything").is_err());
+}
+
+#[test]
+fn log_options_are_taken_from_the_arguments() {
+    let mut args: Vec<String> = ["chess-gui", "--log", "debug", "host", "--log-file", "game.log"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+    let (filter, file) = logging::take_options(&mut args);
+    assert_eq!(filter.as_deref(), Some("debug"));
+    assert_eq!(file, Some("game.log".into()));
+    assert_eq!(args, ["chess-gui", "host"]);
+}
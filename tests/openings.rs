@@ -0,0 +1,108 @@
+// Naming the opening of a game and playing from a Polyglot opening book
+mod common;
+use chess::board::Board;
+use chess_gui::notation::*;
+use chess_gui::openings::Openings;
+use chess_gui::polyglot::{self, Book};
+use chess_gui::session::Session;
+use common::square;
+
+fn play(sans: &[&str]) -> Session {
+    let mut session = Session::new();
+    for san in sans {
+        let (from, to, promotion) = san_to_move(&session.board, san).unwrap();
+        session.make_move(from, to, promotion);
+    }
+    session
+}
+
+fn moves(sans: &[&str]) -> Vec<String> {
+    sans.iter().map(|s| s.to_string()).collect()
+}
+
+// One book entry: key, move, weight and the learn field nobody uses
+fn entry(key: u64, from: &str, to: &str, weight: u16) -> Vec<u8> {
+    let (from, to) = (square(from), square(to));
+    let bits = |p: chess::util::Pos| (7 - p.y as u16) << 3 | p.x as u16;
+    let m = bits(from) << 6 | bits(to);
+    let mut data = key.to_be_bytes().to_vec();
+    data.extend(m.to_be_bytes());
+    data.extend(weight.to_be_bytes());
+    data.extend([0; 4]);
+    data
+}
+
+#[test]
+fn keys_match_the_polyglot_reference() {
+    // The examples from the description of the format
+    assert_eq!(play(&[]).polyglot_key(), 0x463b96181691fc9c);
+    assert_eq!(play(&["e4"]).polyglot_key(), 0x823c9b50fd114196);
+    assert_eq!(play(&["e4", "d5"]).polyglot_key(), 0x0756b94461c50fb0);
+    assert_eq!(play(&["e4", "d5", "e5"]).polyglot_key(), 0x662fafb965db29d4);
+    // White could take on f6 en passant
+    assert_eq!(play(&["e4", "d5", "e5", "f5"]).polyglot_key(), 0x22a48b5a8e47ff78);
+    assert_eq!(play(&["e4", "d5", "e5", "f5", "Ke2"]).polyglot_key(), 0x652a607ca3f242c1);
+}
+
+#[test]
+fn moving_kings_and_rooks_ends_castling() {
+    let session = play(&["e4", "e5", "Ke2", "Nf6", "Ke1", "Ng8", "a4", "h5", "Ra3", "Rh6"]);
+    assert_eq!(polyglot::castling_rights("KQkq", &session.history), "q");
+    let session = play(&["a4", "e5", "Ra3"]);
+    assert_eq!(polyglot::castling_rights("KQkq", &session.history), "Kkq");
+}
+
+#[test]
+fn book_moves_are_picked_by_weight() {
+    let start = play(&[]).polyglot_key();
+    let mut data = entry(start, "d2", "d4", 1);
+    data.extend(entry(start, "e2", "e4", 3));
+    data.extend(entry(start ^ 1, "g1", "f3", 10));
+    let book = Book::parse(&data).unwrap();
+    let board = Board::new();
+
+    let known: Vec<String> = book.moves(&board, start).iter().map(|m| m.uci()).collect();
+    assert_eq!(known, ["d2d4", "e2e4"]);
+    assert_eq!(book.pick(&board, start, 0).unwrap().uci(), "d2d4");
+    for random in 1..4 {
+        assert_eq!(book.pick(&board, start, random).unwrap().uci(), "e2e4");
+    }
+    assert_eq!(book.pick(&board, start, 4).unwrap().uci(), "d2d4");
+    assert!(book.pick(&board, 42, 0).is_none());
+}
+
+#[test]
+fn castling_in_the_book_is_the_kings_move() {
+    let session = play(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5"]);
+    let key = session.polyglot_key();
+    let book = Book::parse(&entry(key, "e1", "h1", 1)).unwrap();
+    let m = book.pick(&session.board, key, 7).unwrap();
+    assert_eq!((m.from, m.to), (square("e1"), square("g1")));
+}
+
+#[test]
+fn broken_books_are_refused() {
+    assert!(Book::parse(&[0; 15]).is_none());
+    assert!(Book::load(std::path::Path::new("/nonexistent/book.bin")).is_err());
+}
+
+#[test]
+fn openings_are_named_by_the_longest_match() {
+    let openings = Openings::embedded();
+    let name = |sans: &[&str]| openings.find(&moves(sans)).map(|o| format!("{} {}", o.eco, o.name));
+    assert_eq!(name(&[]), None);
+    assert_eq!(name(&["e4"]).unwrap(), "B00 King's Pawn Game");
+    assert_eq!(name(&["e4", "c5"]).unwrap(), "B20 Sicilian Defense");
+    assert_eq!(name(&["e4", "e5", "Nf3", "Nc6", "Bb5"]).unwrap(), "C60 Ruy Lopez");
+    // The name stays once the game leaves the list
+    assert_eq!(name(&["e4", "e5", "Nf3", "Nc6", "Bb5", "h6", "h3"]).unwrap(), "C60 Ruy Lopez");
+    assert_eq!(name(&["e4", "e5", "d4", "exd4+"]).unwrap(), "C20 Center Game");
+}
+
+#[test]
+fn openings_are_read_from_tab_separated_lines() {
+    let openings = Openings::parse("eco\tname\tpgn\nA00\tTest\t1. g4 e5 2. f3+ \nbroken line\n");
+    let found = openings.find(&moves(&["g4", "e5", "f3"])).unwrap();
+    assert_eq!((found.eco.as_str(), found.name.as_str()), ("A00", "Test"));
+    assert_eq!(found.moves, ["g4", "e5", "f3"]);
+}
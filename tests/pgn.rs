@@ -0,0 +1,96 @@
+// Moves in standard algebraic notation and games read from and written to PGN
+mod common;
+use chess::board::Board;
+use chess_gui::notation::*;
+use chess_gui::pgn::Pgn;
+use chess_gui::rules::{self, Outcome};
+use common::square;
+
+const GAME: &str = r#"[Event "Casual game"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+
+1. e4 e5 {the king's pawn} 2. Nf3 (2. f4 exf4) 2... Nc6 3. Bb5 $1 a6; the Morphy defence
+4. Ba4 Nf6 *
+"#;
+
+#[test]
+fn moves_are_written_in_san() {
+    let board = Board::new();
+    assert_eq!(move_to_san(&board, square("e2"), square("e4"), None), "e4");
+    assert_eq!(move_to_san(&board, square("g1"), square("f3"), None), "Nf3");
+    assert_eq!(move_to_uci(square("g1"), square("f3"), None), "g1f3");
+}
+
+#[test]
+fn san_and_uci_are_read() {
+    let board = Board::new();
+    assert_eq!(san_to_move(&board, "Nf3").unwrap(), (square("g1"), square("f3"), None));
+    assert_eq!(san_to_move(&board, "e4!?").unwrap(), (square("e2"), square("e4"), None));
+    assert_eq!(uci_to_move("e7e8q"), Some((square("e7"), square("e8"), Some(chess::piece::PieceType::Queen))));
+    assert_eq!(uci_to_move("e7e9"), None);
+}
+
+#[test]
+fn illegal_and_ambiguous_moves_are_refused() {
+    assert_eq!(san_to_move(&Board::new(), "e5").unwrap_err(), "e5 isn't a legal move");
+
+    // Both knights can go to d2
+    let board = board_from_fen("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1").unwrap();
+    let error = san_to_move(&board, "Nd2").unwrap_err();
+    assert!(error.contains("Nbd2") && error.contains("Nfd2"), "{}", error);
+    assert_eq!(san_to_move(&board, "Nbd2").unwrap(), (square("b1"), square("d2"), None));
+    assert_eq!(move_to_san(&board, square("f3"), square("d2"), None), "Nfd2");
+}
+
+#[test]
+fn games_are_parsed() {
+    let pgn = Pgn::parse(GAME).unwrap();
+    assert_eq!(pgn.tag("White"), Some("Anderssen"));
+    assert_eq!(pgn.moves, ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6"]);
+    assert_eq!(pgn.result.as_deref(), Some("*"));
+    assert_eq!(pgn.positions().unwrap().len(), 9);
+}
+
+#[test]
+fn written_games_read_the_same() {
+    let pgn = Pgn::parse(GAME).unwrap();
+    assert_eq!(Pgn::parse(&pgn.to_string()).unwrap(), pgn);
+}
+
+#[test]
+fn illegal_moves_in_a_game_are_reported() {
+    let pgn = Pgn::parse("1. e4 e5 2. Ke3 *").unwrap();
+    assert_eq!(pgn.positions().unwrap_err(), "Move 2: Ke3 isn't a legal move");
+}
+
+#[test]
+fn mate_ends_the_game() {
+    let pgn = Pgn::parse("1. f3 e5 2. g4 Qh4 0-1").unwrap();
+    let board = board_from_fen(pgn.positions().unwrap().last().unwrap()).unwrap();
+    assert_eq!(rules::outcome(&board), Some(Outcome::Checkmate(chess::util::Color::Black)));
+    assert_eq!(rules::outcome(&Board::new()), None);
+}
+
+#[test]
+fn typed_moves_are_read_in_either_notation() {
+    let board = Board::new();
+    assert_eq!(parse_move(&board, "g1f3").unwrap(), (square("g1"), square("f3"), None));
+    assert_eq!(parse_move(&board, " Nf3 ").unwrap(), (square("g1"), square("f3"), None));
+    assert_eq!(parse_move(&board, "g1g3").unwrap_err(), "g1g3 isn't a legal move");
+
+    let board = board_from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(parse_move(&board, "a7a8").unwrap_err(), "a7a8 has to say what the pawn promotes to, e.g. a8=Q");
+    assert_eq!(parse_move(&board, "a7a8n").unwrap(), (square("a7"), square("a8"), Some(chess::piece::PieceType::Knight)));
+}
+
+#[test]
+fn typed_moves_are_completed() {
+    let board = Board::new();
+    assert_eq!(complete(&board, "N"), vec!["Na3", "Nc3", "Nf3", "Nh3"]);
+    assert_eq!(complete(&board, "g1"), vec!["g1f3", "g1h3"]);
+    assert!(complete(&board, "Q").is_empty());
+
+    let board = board_from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(complete(&board, "a"), vec!["a8=B", "a8=N", "a8=Q", "a8=R"]);
+}
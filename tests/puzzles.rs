@@ -0,0 +1,122 @@
+// Solving puzzles from the Lichess puzzle database and rating the player for them
+mod common;
+use chess::util::Color;
+use chess_gui::notation::*;
+use chess_gui::puzzles::*;
+use common::square;
+use std::path::PathBuf;
+
+const DATABASE: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00008,r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24,f2g3 e6e7 b2b1 b3c1 b1c1 h6c1,1913,75,94,6230,crushing hangingPiece long middlegame,https://lichess.org/787zsVup/black#48,
+backrank,6k1/1p3ppp/8/8/8/8/5PPP/R3R1K1 b - - 0 1,b7b6 e1e8,900,80,90,100,backRankMate mate mateIn1 oneMove,,
+castle,4rkr1/4p1pp/8/8/8/8/8/4K2R b K - 0 1,h7h6 h1f1,1200,80,90,100,mate mateIn1 oneMove,,
+";
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chess-gui-puzzles-{}-{}", std::process::id(), name))
+}
+
+fn puzzle(i: usize) -> Puzzle {
+    parse_puzzles(DATABASE).unwrap().remove(i)
+}
+
+fn try_move(attempt: &mut Attempt, uci: &str) -> Verdict {
+    let (from, to, promotion) = uci_to_move(uci).unwrap();
+    attempt.try_move(from, to, promotion)
+}
+
+#[test]
+fn the_database_is_read() {
+    let puzzles = parse_puzzles(DATABASE).unwrap();
+    assert_eq!(puzzles.len(), 3);
+    assert_eq!(puzzles[0].id, "00008");
+    assert_eq!(puzzles[0].moves.len(), 6);
+    assert_eq!(puzzles[0].rating, 1913);
+    assert_eq!(puzzles[0].themes, ["crushing", "hangingPiece", "long", "middlegame"]);
+    assert!(parse_puzzles("1,8/8/8/8/8/8/8/8 w - - 0 1,e2e4,1500").is_err());
+    assert!(parse_puzzles("1,8/8/8/8/8/8/8/8 w - - 0 1,e2e4 e7e5,strong").is_err());
+}
+
+#[test]
+fn the_opponent_moves_first() {
+    let attempt = Attempt::start(puzzle(0)).unwrap();
+    assert_eq!(attempt.color, Color::White);
+    assert!(attempt.is_players_turn());
+    assert_eq!(attempt.last_move.as_ref().unwrap().san, "Bxg3");
+    assert_eq!(attempt.solution().unwrap(), "Rxe7");
+}
+
+#[test]
+fn the_solution_is_played_through() {
+    let mut attempt = Attempt::start(puzzle(0)).unwrap();
+    assert_eq!(try_move(&mut attempt, "e6e7"), Verdict::Correct);
+    assert!(!attempt.is_players_turn());
+    assert_eq!(attempt.opponent_move().unwrap().san, "Qb1");
+    assert_eq!(try_move(&mut attempt, "b3c1"), Verdict::Correct);
+    assert_eq!(attempt.opponent_move().unwrap().san, "Qxc1");
+    assert_eq!(try_move(&mut attempt, "h6c1"), Verdict::Solved);
+    assert!(attempt.is_solved());
+    assert!(attempt.opponent_move().is_none());
+}
+
+#[test]
+fn a_wrong_move_ends_the_attempt() {
+    let mut attempt = Attempt::start(puzzle(0)).unwrap();
+    assert_eq!(try_move(&mut attempt, "h2g3"), Verdict::Wrong);
+    assert!(attempt.is_over());
+    assert!(!attempt.is_solved());
+    // The wrong move isn't made
+    assert!(attempt.board.board[square("h2").y as usize][square("h2").x as usize].is_some());
+}
+
+#[test]
+fn any_mate_solves_the_last_move() {
+    let mut attempt = Attempt::start(puzzle(1)).unwrap();
+    assert_eq!(try_move(&mut attempt, "a1a8"), Verdict::Solved);
+    let mut attempt = Attempt::start(puzzle(1)).unwrap();
+    assert_eq!(try_move(&mut attempt, "e1e7"), Verdict::Wrong);
+}
+
+#[test]
+fn castling_into_mate_solves_the_last_move() {
+    // The rook mates from f1 whether it gets there by itself or by castling
+    let mut attempt = Attempt::start(puzzle(2)).unwrap();
+    assert_eq!(try_move(&mut attempt, "e1g1"), Verdict::Solved);
+    assert!(attempt.board.board[square("g1").y as usize][square("g1").x as usize].is_some());
+}
+
+#[test]
+fn broken_puzzles_are_refused() {
+    let broken = Puzzle { fen: "not a position".to_string(), ..puzzle(1) };
+    assert!(Attempt::start(broken).is_err());
+    let illegal = Puzzle { moves: vec!["b7b4".to_string(), "e1e8".to_string()], ..puzzle(1) };
+    assert!(Attempt::start(illegal).is_err());
+}
+
+#[test]
+fn ratings_move_like_elo() {
+    assert_eq!(new_rating(1500, 1500, true), 1516);
+    assert_eq!(new_rating(1500, 1500, false), 1484);
+    assert!(new_rating(1500, 1900, true) > 1516);
+    assert!(new_rating(1500, 1100, false) < 1484);
+}
+
+#[test]
+fn history_is_kept_in_a_file() {
+    let path = temp_file("history");
+    let _ = std::fs::remove_file(&path);
+    let mut history = History::load(&path).unwrap();
+    assert_eq!(history.rating(), START_RATING);
+
+    let puzzles = &parse_puzzles(DATABASE).unwrap()[..2];
+    // 1913 is closer to a new player's rating than 900
+    assert_eq!(history.next(puzzles).unwrap().id, "00008");
+    history.record(&path, &puzzles[1], true).unwrap();
+    history.record(&path, &puzzles[0], false).unwrap();
+    assert_eq!(history.solved(), 1);
+    assert!(history.next(puzzles).is_none());
+
+    let loaded = History::load(&path).unwrap();
+    assert_eq!(loaded, history);
+    assert_eq!(loaded.rating(), new_rating(new_rating(START_RATING, 900, true), 1913, false));
+}
@@ -0,0 +1,97 @@
+// Records games played over in memory channels and checks that replaying them ends in the same
+// position
+mod common;
+use chess_gui::networking::{c2s_message, C2sMessage, PlayerIdentity};
+use chess_gui::notation::*;
+use chess_gui::recording::{Recording, Replay, Role};
+use chess_gui::session::{Event, Session};
+use chess_gui::transport;
+use common::{poll_both_until, wait_until};
+use prost::Message;
+use std::path::{Path, PathBuf};
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chess-gui-recording-{}-{}", std::process::id(), name))
+}
+
+// Plays a short game with the host recording to `host_file` and the client to `client_file`.
+// Returns the final position.
+fn play(host_file: &Path, client_file: &Path) -> String {
+    let (host_end, client_end) = transport::channel();
+    let mut host = Session::host_over(host_end, Default::default());
+    host.start_recording(host_file).unwrap();
+    let mut client = Session::client(client_end, false, Default::default());
+    client.start_recording(client_file).unwrap();
+    poll_both_until(&mut host, &mut client, Event::Connected { success: true });
+
+    // e2e4 e7e5 g1f3
+    for (i, (from, to)) in [(52, 36), (12, 28), (62, 45)].into_iter().enumerate() {
+        if i % 2 == 0 {
+            host.make_move(square_to_pos(from), square_to_pos(to), None);
+            poll_both_until(&mut host, &mut client, Event::Moved);
+        } else {
+            client.make_move(square_to_pos(from), square_to_pos(to), None);
+            poll_both_until(&mut client, &mut host, Event::Moved);
+        }
+    }
+    assert_eq!(board_to_fen(&host.board), board_to_fen(&client.board));
+    board_to_fen(&host.board)
+}
+
+fn replay(file: &Path) -> String {
+    let (mut replay, mut session) = Replay::new(Recording::load(file).unwrap());
+    wait_until("the replay to finish", || {
+        replay.step(&mut session);
+        session.poll();
+        replay.is_finished()
+    });
+    // Whatever was fed in last still has to be handled
+    session.poll();
+    board_to_fen(&session.board)
+}
+
+#[test]
+fn replays_end_in_the_recorded_position() {
+    let host_file = temp_file("host");
+    let client_file = temp_file("client");
+    let position = play(&host_file, &client_file);
+
+    assert_eq!(Recording::load(&host_file).unwrap().role, Role::Host);
+    assert_eq!(Recording::load(&client_file).unwrap().role, Role::Client);
+    assert_eq!(replay(&host_file), position);
+    assert_eq!(replay(&client_file), position);
+}
+
+#[test]
+fn malformed_recording_is_refused() {
+    let file = temp_file("malformed");
+    std::fs::write(&file, "# chess-gui session client started at 0\n12 < zz\n").unwrap();
+    assert!(Recording::load(&file).is_err());
+    std::fs::write(&file, "not a recording\n").unwrap();
+    assert!(Recording::load(&file).is_err());
+}
+
+#[test]
+fn passwords_and_tokens_are_not_recorded() {
+    let file = temp_file("secrets");
+    let identity = |name: &str| PlayerIdentity {
+        name: name.to_string(),
+        token: b"secret-token".to_vec(),
+        password: Some("hunter2".to_string()),
+    };
+    let (host_end, client_end) = transport::channel();
+    let mut host = Session::host_over(host_end, identity("Alice"));
+    host.start_recording(&file).unwrap();
+    let mut client = Session::client(client_end, false, identity("Bob"));
+    poll_both_until(&mut host, &mut client, Event::Connected { success: true });
+
+    let text = std::fs::read_to_string(&file).unwrap();
+    assert!(text.contains("Bob"), "The connect request wasn't recorded");
+    assert!(!text.contains("hunter2") && !text.contains("secret"), "{}", text);
+    for record in Recording::load(&file).unwrap().records {
+        if let Ok(C2sMessage { msg: Some(c2s_message::Msg::ConnectRequest(r)) }) = C2sMessage::decode(record.data.as_slice()) {
+            let identity = r.identity.unwrap();
+            assert_eq!((identity.password, identity.token), (None, Vec::new()));
+        }
+    }
+}
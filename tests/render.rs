@@ -0,0 +1,100 @@
+// Drawing the board without a window, and comparing it to known good images in tests/golden.
+// UPDATE_GOLDEN=1 writes them again after a deliberate change to how the board looks, a missing
+// one fails the test.
+#![cfg(feature = "headless")]
+
+mod common;
+use chess::board::Board;
+use chess_gui::annotations::Annotation;
+use chess_gui::networking::AnnotationColor;
+use chess_gui::notation::*;
+use chess_gui::raster::{self, Raster};
+use chess_gui::render::BoardView;
+use chess_gui::settings::Theme;
+use common::square;
+use std::path::{Path, PathBuf};
+
+const CELL: f32 = 32.0;
+// Different GPUs and rasterisers blend a little differently
+const TOLERANCE: u8 = 8;
+
+fn pieces() -> &'static Path {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/resources"))
+}
+
+fn golden(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name))
+}
+
+// The pixel in the middle of a square of an unflipped board
+fn middle(raster: &Raster, name: &str) -> [u8; 4] {
+    let pos = square(name);
+    raster.pixel(((pos.x as f32 + 0.5) * CELL) as u32, ((pos.y as f32 + 0.5) * CELL) as u32).unwrap()
+}
+
+fn assert_matches_golden(raster: &Raster, name: &str) {
+    let path = golden(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some_and(|v| v == "1") {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        raster.save_png(&path).unwrap();
+        eprintln!("Wrote {}", path.display());
+        return;
+    }
+    assert!(path.exists(), "{} is missing, UPDATE_GOLDEN=1 writes it", path.display());
+    let expected = Raster::open_png(&path).unwrap();
+    assert_eq!((raster.width(), raster.height()), (expected.width(), expected.height()), "{} has another size", name);
+    for y in 0..raster.height() {
+        for x in 0..raster.width() {
+            let (a, b) = (raster.pixel(x, y).unwrap(), expected.pixel(x, y).unwrap());
+            let close = a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= TOLERANCE);
+            assert!(close, "{} differs at {}, {}: {:?} instead of {:?}", name, x, y, a, b);
+        }
+    }
+}
+
+#[test]
+fn squares_are_drawn_in_the_theme_colors() {
+    let board = board_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let raster = raster::render(&BoardView::plain(&board, false, Theme::Brown, CELL), pieces()).unwrap();
+    let (light, dark) = Theme::Brown.colors();
+    assert_eq!(middle(&raster, "a8"), [light.0, light.1, light.2, 255]);
+    assert_eq!(middle(&raster, "b8"), [dark.0, dark.1, dark.2, 255]);
+    assert_eq!((raster.width(), raster.height()), (256, 256));
+}
+
+#[test]
+fn the_selection_follows_the_orientation() {
+    let board = board_from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let view = BoardView { selected: Some(square("c2")), ..BoardView::plain(&board, true, Theme::Green, CELL) };
+    let raster = raster::render(&view, pieces()).unwrap();
+    // Flipped c2 is where f7 usually is
+    assert_eq!(middle(&raster, "f7"), [0, 85, 71, 255]);
+    assert_ne!(middle(&raster, "c2"), [0, 85, 71, 255]);
+}
+
+#[test]
+fn the_start_position_looks_the_same() {
+    let board = Board::new();
+    let raster = raster::render(&BoardView::plain(&board, false, Theme::Green, CELL), pieces()).unwrap();
+    assert_matches_golden(&raster, "start");
+}
+
+#[test]
+fn highlights_and_annotations_look_the_same() {
+    let board = Board::new();
+    let highlights = [square("f3"), square("h3")];
+    let annotations = [
+        Annotation { from: square("e2"), to: square("e4"), color: AnnotationColor::Green },
+        Annotation { from: square("d5"), to: square("d5"), color: AnnotationColor::Red },
+    ];
+    let view = BoardView {
+        selected: Some(square("g1")),
+        highlights: &highlights,
+        marked: &[square("b1")],
+        annotations: &annotations,
+        large_outlines: true,
+        ..BoardView::plain(&board, true, Theme::HighContrast, CELL)
+    };
+    let raster = raster::render(&view, pieces()).unwrap();
+    assert_matches_golden(&raster, "annotated");
+}
@@ -0,0 +1,320 @@
+// Plays games through the standalone server over loopback, with scripted clients that create and
+// join games through the lobby.
+mod common;
+use chess::board::Board;
+use chess_gui::networking::{self, c2s_message, s2c_message, C2sMessage, ColorPreference, PlayerIdentity, S2cMessage};
+use chess_gui::notation::*;
+use chess_gui::server::Server;
+use chess_gui::session::{Event, Session};
+use common::{poll_for, TIMEOUT};
+use prost::Message;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+struct ScriptedPeer {
+    stream: TcpStream,
+}
+
+impl ScriptedPeer {
+    fn connect(addr: SocketAddr) -> ScriptedPeer {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        ScriptedPeer { stream }
+    }
+
+    fn send(&mut self, msg: c2s_message::Msg) {
+        self.stream.write_all(&C2sMessage { msg: Some(msg) }.encode_to_vec()).unwrap();
+    }
+
+    fn receive(&mut self) -> s2c_message::Msg {
+        let mut buf = [0_u8; 512];
+        let n = self.stream.read(&mut buf).expect("Peer didn't get a message");
+        S2cMessage::decode(&buf[..n]).expect("Peer couldn't decode the message").msg.expect("Empty message")
+    }
+
+    fn connect_ack(&mut self) -> networking::S2cConnectAck {
+        match self.receive() {
+            s2c_message::Msg::ConnectAck(ack) => ack,
+            other => panic!("Expected a connect ack, got {:?}", other),
+        }
+    }
+
+    fn move_ack(&mut self) -> networking::S2cMoveAck {
+        match self.receive() {
+            s2c_message::Msg::MoveAck(ack) => ack,
+            other => panic!("Expected a move ack, got {:?}", other),
+        }
+    }
+
+    fn play(&mut self, m: &str) {
+        self.send(c2s_message::Msg::Move(uci_move(m)));
+    }
+}
+
+// Runs a server on its own thread for the rest of the test
+fn start_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    std::thread::spawn(move || Server::new(listener, None).run());
+    addr
+}
+
+fn start_fen() -> String {
+    board_to_fen(&Board::new())
+}
+
+fn identity(name: &str) -> Option<PlayerIdentity> {
+    Some(PlayerIdentity { name: name.to_string(), token: name.as_bytes().to_vec(), password: None })
+}
+
+fn uci_move(m: &str) -> networking::Move {
+    let (from, to, promotion) = uci_to_move(m).unwrap();
+    networking::Move { from_square: pos_to_square(from), to_square: pos_to_square(to), promotion: promotion_to_proto(promotion) }
+}
+
+// Creates a game as white, returns the creator and the game's id
+fn create(addr: SocketAddr) -> (ScriptedPeer, u64) {
+    let mut white = ScriptedPeer::connect(addr);
+    white.send(c2s_message::Msg::CreateGame(networking::C2sCreateGame {
+        creator_name: "Alice".to_string(),
+        color_preference: ColorPreference::White as i32,
+        identity: identity("Alice"),
+        ..Default::default()
+    }));
+    let ack = white.connect_ack();
+    assert!(ack.success, "{:?}", ack.error);
+    assert_eq!(ack.client_is_white, Some(true));
+    (white, ack.game_id.unwrap())
+}
+
+fn join(addr: SocketAddr, game_id: u64, name: &str) -> (ScriptedPeer, networking::S2cConnectAck) {
+    let mut peer = ScriptedPeer::connect(addr);
+    peer.send(c2s_message::Msg::JoinGame(networking::C2sJoinGame {
+        game_id,
+        player_name: name.to_string(),
+        identity: identity(name),
+        ..Default::default()
+    }));
+    let ack = peer.connect_ack();
+    (peer, ack)
+}
+
+// A game with both seats taken
+fn start_game(addr: SocketAddr) -> (ScriptedPeer, ScriptedPeer, u64) {
+    let (mut white, game_id) = create(addr);
+    let (black, ack) = join(addr, game_id, "Bob");
+    assert!(ack.success, "{:?}", ack.error);
+    assert_eq!(ack.client_is_white, Some(false));
+    assert_eq!(ack.starting_position.unwrap().fen_string, start_fen());
+    assert_eq!((ack.white_name.as_deref(), ack.black_name.as_deref()), (Some("Alice"), Some("Bob")));
+    // The creator learns who joined
+    match white.receive() {
+        s2c_message::Msg::Players(p) => assert_eq!(p.black_name.as_deref(), Some("Bob")),
+        other => panic!("Expected the players, got {:?}", other),
+    }
+    (white, black, game_id)
+}
+
+fn list_games(peer: &mut ScriptedPeer) -> Vec<networking::GameInfo> {
+    peer.send(c2s_message::Msg::ListGames(networking::C2sListGames {}));
+    match peer.receive() {
+        s2c_message::Msg::GameList(list) => list.games,
+        other => panic!("Expected the game list, got {:?}", other),
+    }
+}
+
+#[test]
+fn open_games_are_listed_until_they_are_full() {
+    let addr = start_server();
+    let (_white, game_id) = create(addr);
+    let mut browser = ScriptedPeer::connect(addr);
+    let games = list_games(&mut browser);
+    assert_eq!(games.len(), 1);
+    assert_eq!((games[0].game_id, games[0].creator_name.as_str()), (game_id, "Alice"));
+    assert_eq!(games[0].color_preference, ColorPreference::White as i32);
+
+    let (_black, ack) = join(addr, game_id, "Bob");
+    assert!(ack.success);
+    assert!(list_games(&mut browser).is_empty());
+    // Nobody else gets a seat
+    let (_, ack) = join(addr, game_id, "Carol");
+    assert!(!ack.success);
+    let (_, ack) = join(addr, game_id + 1, "Carol");
+    assert!(!ack.success);
+}
+
+#[test]
+fn games_created_by_connecting_list_their_creator() {
+    let addr = start_server();
+    let mut white = ScriptedPeer::connect(addr);
+    white.send(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+        game_id: 7,
+        identity: identity("Alice"),
+        ..Default::default()
+    }));
+    assert!(white.connect_ack().success);
+    let games = list_games(&mut ScriptedPeer::connect(addr));
+    assert_eq!(games.len(), 1);
+    assert_eq!((games[0].game_id, games[0].creator_name.as_str()), (7, "Alice"));
+}
+
+#[test]
+fn legal_moves_are_acknowledged_and_passed_on() {
+    let addr = start_server();
+    let (mut white, mut black, _) = start_game(addr);
+
+    white.play("e2e4");
+    let ack = white.move_ack();
+    assert!(ack.legal);
+    let fen = ack.board_result.unwrap().fen_string;
+    assert!(fen.starts_with("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b "), "{}", fen);
+    match black.receive() {
+        s2c_message::Msg::Move(m) => assert_eq!(m, uci_move("e2e4")),
+        other => panic!("Expected a move, got {:?}", other),
+    }
+
+    black.play("e7e5");
+    assert!(black.move_ack().legal);
+    assert!(matches!(white.receive(), s2c_message::Msg::Move(m) if m == uci_move("e7e5")));
+}
+
+#[test]
+fn illegal_and_out_of_turn_moves_are_refused() {
+    let addr = start_server();
+    let (mut white, mut black, _) = start_game(addr);
+
+    // Black isn't to move
+    black.play("e7e5");
+    let ack = black.move_ack();
+    assert!(!ack.legal);
+    assert_eq!(ack.board_result.unwrap().fen_string, start_fen());
+    // A pawn can't move three squares
+    white.play("e2e5");
+    assert!(!white.move_ack().legal);
+    // Neither can a piece of the other side be moved
+    white.play("e7e5");
+    assert!(!white.move_ack().legal);
+
+    // Nothing was passed on, the next thing black gets is white's legal move
+    white.play("d2d4");
+    assert!(white.move_ack().legal);
+    assert!(matches!(black.receive(), s2c_message::Msg::Move(m) if m == uci_move("d2d4")));
+}
+
+#[test]
+fn nobody_moves_until_both_seats_are_taken() {
+    let addr = start_server();
+    let (mut white, _) = create(addr);
+    white.play("e2e4");
+    let ack = white.move_ack();
+    assert!(!ack.legal);
+    assert_eq!(ack.board_result.unwrap().fen_string, start_fen());
+}
+
+#[test]
+fn disconnected_players_can_take_their_seat_back() {
+    let addr = start_server();
+    let (mut white, black, game_id) = start_game(addr);
+    drop(black);
+    // The server notices on its next read, until then moves may still go through
+    std::thread::sleep(Duration::from_millis(100));
+
+    white.play("e2e4");
+    assert!(!white.move_ack().legal, "A move was made without an opponent");
+    // The empty seat belongs to the player who left
+    let (_, ack) = join(addr, game_id, "Carol");
+    assert!(!ack.success);
+    let (mut black, ack) = join(addr, game_id, "Bob");
+    assert!(ack.success, "{:?}", ack.error);
+    assert_eq!(ack.client_is_white, Some(false));
+    assert!(matches!(white.receive(), s2c_message::Msg::Players(_)));
+
+    white.play("e2e4");
+    assert!(white.move_ack().legal);
+    assert!(matches!(black.receive(), s2c_message::Msg::Move(_)));
+}
+
+#[test]
+fn games_everyone_left_are_removed() {
+    let addr = start_server();
+    let (white, black, game_id) = start_game(addr);
+    drop(white);
+    drop(black);
+    std::thread::sleep(Duration::from_millis(100));
+    let (_, ack) = join(addr, game_id, "Alice");
+    assert!(!ack.success);
+    assert_eq!(ack.error.as_deref(), Some(format!("There is no game {}", game_id).as_str()));
+}
+
+#[test]
+fn nobody_moves_while_the_white_seat_is_empty() {
+    let addr = start_server();
+    let (mut white, mut black, _) = start_game(addr);
+    white.play("e2e4");
+    assert!(white.move_ack().legal);
+    assert!(matches!(black.receive(), s2c_message::Msg::Move(_)));
+    drop(white);
+    std::thread::sleep(Duration::from_millis(100));
+
+    black.play("e7e5");
+    assert!(!black.move_ack().legal, "A move was made without an opponent");
+}
+
+#[test]
+fn long_game_lists_fit_into_one_read() {
+    let addr = start_server();
+    // Kept open until the list has been read
+    let creators: Vec<(ScriptedPeer, u64)> = (0..60).map(|_| create(addr)).collect();
+    let mut browser = ScriptedPeer::connect(addr);
+    let games = list_games(&mut browser);
+    assert!(!games.is_empty() && games.len() < creators.len(), "{} games listed", games.len());
+    // The oldest games come first
+    let ids: Vec<u64> = games.iter().map(|g| g.game_id).collect();
+    let oldest: Vec<u64> = creators.iter().map(|(_, id)| *id).take(ids.len()).collect();
+    assert_eq!(ids, oldest);
+}
+
+// A player using the lobby the way the GUI does
+fn lobby(addr: SocketAddr, name: &str) -> Session {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nonblocking(true).unwrap();
+    Session::lobby(stream, identity(name).unwrap())
+}
+
+#[test]
+fn lobby_players_are_connected_despite_the_messages_after_the_ack() {
+    let addr = start_server();
+    let mut alice = lobby(addr, "Alice");
+    alice.create_game(Default::default(), ColorPreference::White);
+    // The server announces the new player in the chat straight after the ack
+    let connected = poll_for(&mut alice, |e| match e {
+        Event::Connected { success } => Some(*success),
+        _ => None,
+    });
+    assert!(connected);
+
+    let mut bob = lobby(addr, "Bob");
+    let game_id = poll_for(&mut bob, |e| match e {
+        Event::GameList(games) => games.first().map(|g| g.game_id),
+        _ => None,
+    });
+    bob.join_game(game_id);
+    let connected = poll_for(&mut bob, |e| match e {
+        Event::Connected { success } => Some(*success),
+        _ => None,
+    });
+    assert!(connected);
+    assert_eq!(bob.player_name(chess::util::Color::White), Some("Alice"));
+    // Alice hears about it in the chat, after the announcement of her own arrival
+    let texts: Vec<String> = (0..2)
+        .map(|_| {
+            poll_for(&mut alice, |e| match e {
+                Event::Chat(m) => Some(m.text.clone()),
+                _ => None,
+            })
+        })
+        .collect();
+    assert_eq!(texts, ["Alice (white) joined the game", "Bob (black) joined the game"]);
+}
@@ -0,0 +1,81 @@
+// Loading and saving the settings file and overriding settings on the command line
+use chess_gui::settings::{Orientation, Settings, Theme, TimeControl};
+use std::path::PathBuf;
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("chess-gui-settings-{}-{}", std::process::id(), name))
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+#[test]
+fn missing_file_gives_defaults() {
+    assert_eq!(Settings::load(&temp_file("missing")).unwrap(), Settings::default());
+}
+
+#[test]
+fn saved_settings_load_the_same() {
+    let path = temp_file("saved").join("settings.toml");
+    let settings = Settings {
+        name: "Magnus".to_string(),
+        theme: Theme::Brown,
+        orientation: Orientation::Black,
+        sound: false,
+        engine_path: Some("/usr/bin/stockfish".into()),
+        time_control: TimeControl { minutes: 5, increment: 3 },
+        ..Settings::default()
+    };
+    settings.save(&path).unwrap();
+    assert_eq!(Settings::load(&path).unwrap(), settings);
+}
+
+#[test]
+fn missing_fields_get_defaults() {
+    let path = temp_file("partial");
+    std::fs::write(&path, "port = 4000\ntheme = \"blue\"\n").unwrap();
+    let settings = Settings::load(&path).unwrap();
+    assert_eq!(settings.port, 4000);
+    assert_eq!(settings.theme, Theme::Blue);
+    assert_eq!(settings.name, Settings::default().name);
+}
+
+#[test]
+fn broken_file_is_an_error() {
+    let path = temp_file("broken");
+    std::fs::write(&path, "theme = \"plaid\"\n").unwrap();
+    assert!(Settings::load(&path).is_err());
+}
+
+#[test]
+fn out_of_range_values_in_the_file_are_an_error() {
+    let path = temp_file("out-of-range");
+    std::fs::write(&path, "volume = 101\n").unwrap();
+    assert!(Settings::load(&path).is_err());
+    for size in [15, 4097] {
+        std::fs::write(&path, format!("export_size = {}\n", size)).unwrap();
+        assert!(Settings::load(&path).is_err(), "Export size {} was accepted", size);
+    }
+    std::fs::write(&path, "volume = 100\nexport_size = 4096\n").unwrap();
+    assert!(Settings::load(&path).is_ok());
+}
+
+#[test]
+fn arguments_override_settings() {
+    let mut settings = Settings::default();
+    let mut a = args(&["chess-gui", "client", "--port", "4000", "--theme", "gray", "--name", "Judit", "--mute"]);
+    settings.apply_args(&mut a).unwrap();
+    assert_eq!(a, args(&["chess-gui", "client"]));
+    assert_eq!(settings.port, 4000);
+    assert_eq!(settings.theme, Theme::Gray);
+    assert_eq!(settings.name, "Judit");
+    assert!(!settings.sound);
+    assert_eq!(settings.address(), "127.0.0.1:4000");
+}
+
+#[test]
+fn invalid_overrides_are_refused() {
+    assert!(Settings::default().apply_args(&mut args(&["chess-gui", "--port", "lots"])).is_err());
+    assert!(Settings::default().apply_args(&mut args(&["chess-gui", "--orientation", "sideways"])).is_err());
+}
@@ -0,0 +1,21 @@
+// Picking the sound effect for a move
+mod common;
+use chess_gui::sound::Sound;
+
+// The sound for the last of `moves`, given in UCI notation, played from the start
+fn sound_after(moves: &[&str]) -> Sound {
+    common::after(moves, Sound::for_move)
+}
+
+#[test]
+fn moves_captures_and_checks_sound_different() {
+    assert_eq!(sound_after(&["e2e4"]), Sound::Move);
+    assert_eq!(sound_after(&["e2e4", "d7d5", "e4d5"]), Sound::Capture);
+    assert_eq!(sound_after(&["e2e4", "f7f6", "d1h5"]), Sound::Check);
+}
+
+#[test]
+fn castling_has_its_own_sound() {
+    assert_eq!(sound_after(&["e2e4", "e7e5", "g1f3", "b8c6", "f1c4", "g8f6", "e1g1"]), Sound::Castle);
+}
+
@@ -0,0 +1,108 @@
+// TLS between a host and a client over loopback, with the certificates kept in temporary directories
+mod common;
+use chess_gui::notation::square_to_pos;
+use chess_gui::session::{Event, Session};
+use chess_gui::stream::Stream;
+use chess_gui::tls::{self, Identity, KnownPeers};
+use common::{poll_until, wait_until};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chess-gui-tls-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+// Hosts a game with `identity` and connects a client that checks the certificate against
+// `known_peers`. Returns both sessions, or the client's error.
+fn connect(identity: &Identity, known_peers: KnownPeers) -> std::io::Result<(Session, Session)> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let mut host = Session::host_tls(listener, Default::default(), identity).unwrap();
+
+    // The handshake blocks so the client has to run next to the host polling for it
+    let client = std::thread::spawn(move || {
+        let stream = TcpStream::connect(addr)?;
+        let stream = tls::connect(stream, "test-host", known_peers)?;
+        Ok::<_, std::io::Error>(Session::client(Stream::from(stream), false, Default::default()))
+    });
+    wait_until("the handshake", || {
+        host.poll();
+        client.is_finished()
+    });
+    let client = client.join().unwrap()?;
+    wait_until("the host to accept the client", || {
+        host.poll();
+        host.is_connected()
+    });
+    Ok((host, client))
+}
+
+#[test]
+fn certificate_is_generated_once() {
+    let dir = temp_dir("generated");
+    let first = Identity::load_or_generate(&dir).unwrap();
+    let second = Identity::load_or_generate(&dir).unwrap();
+    assert_eq!(first.fingerprint(), second.fingerprint());
+}
+
+#[test]
+fn play_over_tls() {
+    let dir = temp_dir("play");
+    let identity = Identity::load_or_generate(&dir.join("host")).unwrap();
+    let (mut host, mut client) = connect(&identity, KnownPeers::new(dir.join("known_peers"))).unwrap();
+
+    // The client sees the host's certificate and pins it
+    assert_eq!(client.fingerprint, Some(identity.fingerprint()));
+    assert_eq!(host.fingerprint, Some(identity.fingerprint()));
+    let known_peers = std::fs::read_to_string(dir.join("known_peers")).unwrap();
+    assert!(known_peers.contains(&identity.fingerprint()));
+
+    poll_until(&mut client, Event::Connected { success: true });
+
+    // e2e4
+    host.make_move(square_to_pos(52), square_to_pos(36), None);
+    poll_until(&mut client, Event::Moved);
+}
+
+#[test]
+fn changed_certificate_is_refused() {
+    let dir = temp_dir("changed");
+    let known_peers = dir.join("known_peers");
+    let original = Identity::load_or_generate(&dir.join("original")).unwrap();
+    assert!(connect(&original, KnownPeers::new(known_peers.clone())).is_ok());
+
+    // Same host name, different certificate
+    let impostor = Identity::load_or_generate(&dir.join("impostor")).unwrap();
+    assert!(connect(&impostor, KnownPeers::new(known_peers)).is_err());
+}
+
+#[test]
+fn a_silent_connection_doesnt_hold_up_the_host() {
+    let dir = temp_dir("silent");
+    let identity = Identity::load_or_generate(&dir.join("host")).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let mut host = Session::host_tls(listener, Default::default(), &identity).unwrap();
+
+    // Connects and never starts its handshake
+    let _silent = TcpStream::connect(addr).unwrap();
+    let start = Instant::now();
+    host.poll();
+    let known_peers = KnownPeers::new(dir.join("known_peers"));
+    let client = std::thread::spawn(move || {
+        let stream = TcpStream::connect(addr)?;
+        tls::connect(stream, "test-host", known_peers)
+    });
+    wait_until("the handshake", || {
+        host.poll();
+        client.is_finished()
+    });
+    assert!(client.join().unwrap().is_ok());
+    // Much less than the time the host gives the silent connection
+    assert!(start.elapsed() < Duration::from_secs(2), "The host waited for the silent connection");
+}
@@ -0,0 +1,105 @@
+// Games between two `Session`s in the same process, connected by in memory channels
+mod common;
+use chess_gui::networking::{self, s2c_message, S2cMessage};
+use chess_gui::notation::board_to_fen;
+use chess_gui::session::{Event, Session};
+use chess_gui::transport::{self, ClientConnection, Transport};
+use common::{poll_both_until, square};
+use prost::Message;
+
+fn connect() -> (Session, Session) {
+    let (host_end, client_end) = transport::channel();
+    let mut host = Session::host_over(host_end, Default::default());
+    let mut client = Session::client(client_end, false, Default::default());
+    poll_both_until(&mut host, &mut client, Event::Connected { success: true });
+    (host, client)
+}
+
+#[test]
+fn play_over_channels() {
+    let (mut host, mut client) = connect();
+    assert!(host.is_connected());
+
+    for (i, m) in ["e2e4", "e7e5", "g1f3", "b8c6"].iter().enumerate() {
+        let from = square(&m[0..2]);
+        let to = square(&m[2..4]);
+        if i % 2 == 0 {
+            host.make_move(from, to, None);
+            poll_both_until(&mut host, &mut client, Event::Moved);
+        } else {
+            client.make_move(from, to, None);
+            poll_both_until(&mut client, &mut host, Event::Moved);
+        }
+    }
+    assert_eq!(board_to_fen(&host.board), board_to_fen(&client.board));
+    assert!(board_to_fen(&host.board).starts_with("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R"));
+}
+
+#[test]
+fn dropped_channel_disconnects() {
+    let (mut host, client) = connect();
+    drop(client);
+    assert!(host.poll().contains(&Event::Disconnected));
+    assert!(!host.is_connected());
+}
+
+#[test]
+fn garbage_from_the_host_is_reported() {
+    let (mut peer, client_end) = transport::channel();
+    let mut client = Session::client(client_end, false, Default::default());
+    peer.send_message(&[0xff, 0xff, 0xff]).unwrap();
+    let events = client.poll();
+    assert!(matches!(events.first(), Some(Event::Error(e)) if e.starts_with("Couldn't decode")));
+    assert!(events.contains(&Event::Disconnected));
+}
+
+#[test]
+fn illegal_move_is_reported_and_not_sent() {
+    let (mut host, mut client) = connect();
+    host.make_move(square("e2"), square("e5"), None);
+    assert!(matches!(host.poll().as_slice(), [Event::Error(_)]));
+    assert!(host.is_connected());
+    assert!(!client.poll().contains(&Event::Moved));
+}
+
+#[test]
+fn failed_send_is_reported_as_lost_connection() {
+    let (mut host, client) = connect();
+    drop(client);
+    host.make_move(square("e2"), square("e4"), None);
+    assert!(host.poll().contains(&Event::Disconnected));
+    assert!(!host.is_connected());
+}
+
+fn chat(text: &str) -> S2cMessage {
+    S2cMessage {
+        msg: Some(s2c_message::Msg::Chat(networking::ChatMessage { text: text.to_string(), ..Default::default() })),
+    }
+}
+
+#[test]
+fn messages_that_arrive_together_are_told_apart() {
+    let (mut peer, end) = transport::channel();
+    let mut connection = ClientConnection::new(end);
+    let mut data = chat("first").encode_to_vec();
+    data.extend(chat("second").encode_to_vec());
+    peer.send_message(&data).unwrap();
+    assert_eq!(connection.poll().unwrap(), Some(chat("first")));
+    assert_eq!(connection.poll().unwrap(), Some(chat("second")));
+    assert_eq!(connection.poll().unwrap(), None);
+}
+
+#[test]
+fn messages_split_over_reads_are_put_back_together() {
+    let (mut peer, end) = transport::channel();
+    let mut connection = ClientConnection::new(end);
+    let data = chat(&"long ".repeat(200)).encode_to_vec();
+    let (start, rest) = data.split_at(300);
+    // Also splits the length after its first byte
+    peer.send_message(&start[..2]).unwrap();
+    assert_eq!(connection.poll().unwrap(), None);
+    peer.send_message(&start[2..]).unwrap();
+    assert_eq!(connection.poll().unwrap(), None);
+    peer.send_message(rest).unwrap();
+    assert_eq!(connection.poll().unwrap(), Some(chat(&"long ".repeat(200))));
+}
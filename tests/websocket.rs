@@ -0,0 +1,119 @@
+// WebSocket clients joining a game hosted by `Session` over loopback, once with a bare WebSocket
+// client like a browser would use and once with `Session` on both ends
+mod common;
+use chess_gui::networking::{self, c2s_message, s2c_message, C2sMessage, S2cMessage};
+use chess_gui::notation::*;
+use chess_gui::session::{Event, Session};
+use chess_gui::stream::Socket;
+use chess_gui::websocket;
+use common::{poll_both_until, poll_until, wait_until, TIMEOUT};
+use prost::Message;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+fn host() -> (Session, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    (Session::host(listener, Default::default()), addr)
+}
+
+// Runs `client` next to the host until it is done, the handshakes block so it needs its own thread
+fn with_host<T: Send + 'static>(host: &mut Session, client: impl FnOnce() -> T + Send + 'static) -> T {
+    let client = std::thread::spawn(client);
+    wait_until("the handshake", || {
+        host.poll();
+        client.is_finished()
+    });
+    client.join().unwrap()
+}
+
+fn receive(ws: &mut tungstenite::WebSocket<TcpStream>) -> S2cMessage {
+    match ws.read().expect("Client didn't get a message") {
+        tungstenite::Message::Binary(data) => S2cMessage::decode(data.as_slice()).unwrap(),
+        other => panic!("Expected a binary frame, got {:?}", other),
+    }
+}
+
+#[test]
+fn browser_client_plays_over_websocket() {
+    let (mut host, addr) = host();
+    let mut ws = with_host(&mut host, move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let (mut ws, _) = tungstenite::client(format!("ws://{}/", addr), stream).unwrap();
+        let request = C2sMessage {
+            msg: Some(c2s_message::Msg::ConnectRequest(networking::C2sConnectRequest {
+                game_id: 0,
+                spectate: false,
+                ..Default::default()
+            })),
+        };
+        ws.send(tungstenite::Message::Binary(request.encode_to_vec())).unwrap();
+        ws
+    });
+    wait_until("the host to accept the client", || {
+        host.poll();
+        host.is_connected()
+    });
+    match receive(&mut ws).msg {
+        Some(s2c_message::Msg::ConnectAck(ack)) => assert!(ack.success),
+        other => panic!("Expected a connect ack, got {:?}", other),
+    }
+
+    host.make_move(square_to_pos(52), square_to_pos(36), None);
+    match receive(&mut ws).msg {
+        Some(s2c_message::Msg::Move(m)) => assert_eq!((m.from_square, m.to_square), (52, 36)),
+        other => panic!("Expected a move, got {:?}", other),
+    }
+
+    // e7e5
+    let reply = C2sMessage {
+        msg: Some(c2s_message::Msg::Move(networking::Move { from_square: 12, to_square: 28, promotion: None })),
+    };
+    ws.send(tungstenite::Message::Binary(reply.encode_to_vec())).unwrap();
+    poll_until(&mut host, Event::Moved);
+    assert_eq!(
+        board_to_fen(&host.board).split(' ').next(),
+        Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR")
+    );
+}
+
+#[test]
+fn session_plays_over_websocket() {
+    let (mut host, addr) = host();
+    let mut client = with_host(&mut host, move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let stream = websocket::connect(Socket::Tcp(stream), &format!("ws://{}/", addr)).unwrap();
+        Session::client(stream, false, Default::default())
+    });
+    poll_both_until(&mut host, &mut client, Event::Connected { success: true });
+
+    host.make_move(square_to_pos(52), square_to_pos(36), None);
+    poll_until(&mut client, Event::Moved);
+    client.make_move(square_to_pos(12), square_to_pos(28), None);
+    poll_until(&mut host, Event::Moved);
+    assert_eq!(board_to_fen(&client.board), board_to_fen(&host.board));
+}
+
+#[test]
+fn half_sent_handshakes_dont_hold_up_the_host() {
+    use std::io::Write;
+    let (mut host, addr) = host();
+    // One stops in the middle of the request line, the other in the middle of the headers
+    let mut sniffed = TcpStream::connect(addr).unwrap();
+    sniffed.write_all(b"GE").unwrap();
+    let mut started = TcpStream::connect(addr).unwrap();
+    started.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n").unwrap();
+    let start = Instant::now();
+    host.poll();
+
+    let mut client = with_host(&mut host, move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let stream = websocket::connect(Socket::Tcp(stream), &format!("ws://{}/", addr)).unwrap();
+        Session::client(stream, false, Default::default())
+    });
+    poll_both_until(&mut host, &mut client, Event::Connected { success: true });
+    // Much less than the time the host gives the other two
+    assert!(start.elapsed() < Duration::from_secs(2), "The host waited for the half sent handshakes");
+}